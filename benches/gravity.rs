@@ -0,0 +1,137 @@
+// MIT License
+
+// Copyright (c) 2022 AnonmousDapper
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use pixie::{
+    force::{Force, Gravity, System},
+    simulation::{Boundary, ForceSolver, Particle, Point, Simulator, Velocity},
+    soa::PointMasses,
+};
+
+const SIZES: [usize; 2] = [1_000, 10_000];
+
+/// A named disc of particles on a golden-angle spiral, so no two coincide
+fn disc(count: usize) -> Vec<Particle> {
+    (0..count)
+        .map(|idx| {
+            let angle = idx as f32 * 2.399_963;
+            let radius = (idx as f32 / count as f32).sqrt() * 120.0;
+            let position = Point::new(128.0, 128.0) + Point::new(angle.cos(), angle.sin()) * radius;
+
+            Particle::new(
+                position,
+                Velocity::ZERO,
+                (255, 255, 255),
+                300.0,
+                1.0,
+                1e9,
+                0.0,
+            )
+            .with_name(&format!("particle {idx}"))
+        })
+        .collect()
+}
+
+fn system<'a>(
+    particles: &'a [Particle],
+    positions: &'a [Point],
+    velocities: &'a [Velocity],
+    solver: ForceSolver,
+    point_masses: Option<&'a PointMasses>,
+) -> System<'a> {
+    System {
+        particles,
+        positions,
+        velocities,
+        boundary: Boundary::Open,
+        world_size: Point::splat(256.0),
+        physics_scale: 1.0,
+        softening: 0.5,
+        solver,
+        theta: 0.5,
+        point_masses,
+    }
+}
+
+/// One gravity evaluation, as the integrators make several times a step
+fn gravity(c: &mut Criterion) {
+    let mut group = c.benchmark_group("gravity");
+    group.sample_size(10);
+
+    for count in SIZES {
+        let particles = disc(count);
+        let positions: Vec<Point> = particles.iter().map(Particle::position).collect();
+        let velocities = vec![Velocity::ZERO; count];
+        let mut accel = vec![Velocity::ZERO; count];
+
+        // the current layout: pairs walked over `Particle`s, names and all
+        group.bench_with_input(BenchmarkId::new("aos", count), &count, |b, _| {
+            let system = system(
+                &particles,
+                &positions,
+                &velocities,
+                ForceSolver::Direct,
+                None,
+            );
+
+            b.iter(|| {
+                accel.fill(Velocity::ZERO);
+                Gravity.accumulate(black_box(&system), &mut accel);
+            })
+        });
+
+        // a position/mass cache kept across evaluations, as the simulator does, refreshed in place
+        let mut point_masses = PointMasses::from_particles(&particles);
+
+        group.bench_with_input(BenchmarkId::new("soa", count), &count, |b, _| {
+            b.iter(|| {
+                point_masses.set_positions(black_box(&positions));
+
+                let system = system(
+                    &particles,
+                    &positions,
+                    &velocities,
+                    ForceSolver::Vectorised,
+                    Some(&point_masses),
+                );
+
+                accel.fill(Velocity::ZERO);
+                Gravity.accumulate(&system, &mut accel);
+            })
+        });
+    }
+
+    group.finish();
+}
+
+/// A whole physics step through the simulator with each solver
+fn step(c: &mut Criterion) {
+    let mut group = c.benchmark_group("step");
+    group.sample_size(10);
+
+    for count in SIZES {
+        for (label, solver) in [
+            ("aos", ForceSolver::Direct),
+            ("soa", ForceSolver::Vectorised),
+        ] {
+            let mut sim = Simulator::new();
+            sim.set_solver(solver);
+            sim.set_softening(0.5);
+
+            for particle in disc(count) {
+                sim.add_particle(particle);
+            }
+
+            group.bench_with_input(BenchmarkId::new(label, count), &count, |b, _| {
+                b.iter(|| sim.step_physics(black_box(1e-3)))
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, gravity, step);
+criterion_main!(benches);
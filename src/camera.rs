@@ -0,0 +1,163 @@
+// MIT License
+
+// Copyright (c) 2022 AnonmousDapper
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    canvas,
+    simulation::{ParticleId, Point},
+    FB_HEIGHT, FB_WIDTH,
+};
+
+fn default_zoom() -> f32 {
+    1.0
+}
+
+fn default_viewport() -> Point {
+    Point::new(FB_WIDTH as f32, FB_HEIGHT as f32)
+}
+
+/// Maps world space on to the canvas
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Camera {
+    /// World point shown at the middle of the canvas
+    #[serde(default)]
+    pub center: Point,
+
+    /// Canvas pixels per world unit
+    #[serde(default = "default_zoom")]
+    pub zoom: f32,
+
+    /// Particle to keep centred
+    #[serde(skip)]
+    pub follow: Option<ParticleId>,
+
+    /// Name of the particle to keep centred, as written in scenes; the simulator turns it into `follow` on load
+    #[serde(default, rename = "follow")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub follow_name: Option<String>,
+
+    /// Canvas size in pixels, kept in step with the simulator's resolution
+    #[serde(skip, default = "default_viewport")]
+    viewport: Point,
+}
+
+impl Camera {
+    pub fn new(center: Point, zoom: f32, viewport: Point) -> Self {
+        Self {
+            center,
+            zoom,
+            follow: None,
+            follow_name: None,
+            viewport,
+        }
+    }
+
+    /// Centre on a world of the given size, zoomed so all of it fits the viewport
+    pub fn fit(world_size: Point, viewport: Point) -> Self {
+        let zoom = (viewport / world_size).min_element();
+
+        Self::new(world_size * 0.5, zoom, viewport)
+    }
+
+    pub fn viewport(&self) -> Point {
+        self.viewport
+    }
+
+    /// Change the canvas size; zoom is left alone, so more or less of the world comes into view
+    pub fn set_viewport(&mut self, viewport: Point) {
+        self.viewport = viewport;
+    }
+
+    pub fn world_to_canvas(&self, position: Point) -> Point {
+        (position - self.center) * self.zoom + self.viewport * 0.5
+    }
+
+    pub fn canvas_to_world(&self, position: Point) -> Point {
+        (position - self.viewport * 0.5) / self.zoom + self.center
+    }
+
+    /// Canvas pixel for a world position, if it's in view
+    pub fn project(&self, position: Point) -> Option<canvas::Point> {
+        let pixel = self.world_to_canvas(position).round();
+
+        if pixel.cmpge(Point::ZERO).all() && pixel.cmplt(self.viewport).all() {
+            Some((pixel.x as canvas::Size, pixel.y as canvas::Size))
+        } else {
+            None
+        }
+    }
+
+    /// Canvas size of a world length, never less than a pixel
+    pub fn project_size(&self, size: f32) -> canvas::Size {
+        ((size * self.zoom).round() as canvas::Size).max(1)
+    }
+
+    /// Move the view by a number of canvas pixels
+    pub fn pan(&mut self, pixels: Point) {
+        self.center += pixels / self.zoom;
+    }
+
+    pub fn zoom_by(&mut self, factor: f32) {
+        self.zoom *= factor;
+    }
+
+    pub fn follow(&mut self, id: ParticleId) {
+        self.follow = Some(id);
+    }
+
+    pub fn unfollow(&mut self) {
+        self.follow = None;
+        self.follow_name = None;
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new(default_viewport() * 0.5, 1.0, default_viewport())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trips(camera: &Camera) {
+        for position in [
+            Point::ZERO,
+            Point::new(12.5, -40.0),
+            Point::new(300.0, 170.25),
+        ] {
+            let back = camera.canvas_to_world(camera.world_to_canvas(position));
+
+            assert!(
+                (back - position).abs().max_element() < 1e-3,
+                "{position} came back as {back}"
+            );
+        }
+    }
+
+    #[test]
+    fn world_and_canvas_round_trip() {
+        let mut camera = Camera::fit(Point::new(512.0, 256.0), Point::new(320.0, 240.0));
+        round_trips(&camera);
+
+        camera.zoom_by(3.0);
+        camera.pan(Point::new(-25.0, 10.0));
+        round_trips(&camera);
+
+        // following a particle keeps it centred, wherever it is and however far in
+        let particle = Point::new(401.0, 33.0);
+        camera.center = particle;
+        round_trips(&camera);
+
+        assert_eq!(camera.world_to_canvas(particle), camera.viewport() * 0.5);
+        assert_eq!(camera.project(particle), Some((160, 120)));
+
+        // a world unit covers `zoom` pixels
+        let step = camera.world_to_canvas(particle + Point::X) - camera.world_to_canvas(particle);
+        assert!((step.x - camera.zoom).abs() < 1e-3);
+    }
+}
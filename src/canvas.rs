@@ -2,24 +2,59 @@
 
 // Copyright (c) 2022 AnonmousDapper
 
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+};
+
 use line_drawing::{Bresenham, BresenhamCircle};
 
 use half::f16;
 
-use crate::{FB_HEIGHT, FB_WIDTH};
+use glam::Vec3;
 
-const BUF_LEN: usize = FB_WIDTH as usize * FB_HEIGHT as usize * 8;
+use crate::{
+    pipeline::{luminance, PostSettings},
+    FB_HEIGHT, FB_WIDTH,
+};
 
-const GAMMA: f32 = 2.2;
+/// Gamma that authored colors are encoded with
+pub const GAMMA: f32 = 2.2;
 
 pub type Size = i32;
 pub type Point = (Size, Size);
 
-type Buffer = Vec<u8>;
+/// RGBA16F pixel data, row-major
+struct Buffer {
+    data: Vec<u8>,
+    width: Size,
+    height: Size,
+}
+
+impl Buffer {
+    fn new(width: Size, height: Size) -> Self {
+        Self {
+            data: vec![0; width as usize * height as usize * 8],
+            width,
+            height,
+        }
+    }
+
+    #[inline]
+    fn as_idx(&self, x: Size, y: Size) -> usize {
+        (y * 8 * self.width + x * 8) as usize
+    }
 
-#[inline]
-fn as_idx(x: Size, y: Size) -> usize {
-    (y * 8 * FB_WIDTH + x * 8) as usize
+    /// Raw RGBA channels of a pixel
+    #[inline]
+    fn get_pixel(&self, x: Size, y: Size) -> [f16; 4] {
+        let idx = self.as_idx(x, y);
+        let channel = |c: usize| f16::from_le_bytes([self.data[idx + c], self.data[idx + c + 1]]);
+
+        [channel(0), channel(2), channel(4), channel(6)]
+    }
 }
 
 pub trait Surface {
@@ -39,27 +74,27 @@ impl Surface for Buffer {
 
     #[inline]
     fn set_pixel(&mut self, x: Size, y: Size, data: &Self::Pixel) {
-        let idx = as_idx(x.rem_euclid(FB_WIDTH), y.rem_euclid(FB_HEIGHT));
-        self[idx..idx + 8].copy_from_slice(data);
+        let idx = self.as_idx(x.rem_euclid(self.width), y.rem_euclid(self.height));
+        self.data[idx..idx + 8].copy_from_slice(data);
     }
 
     #[inline]
     fn set_row(&mut self, y: Size, data: &Self::Pixel) {
-        for x in 0..FB_WIDTH {
+        for x in 0..self.width {
             self.set_pixel(x, y, data);
         }
     }
 
     #[inline]
     fn set_col(&mut self, x: Size, data: &Self::Pixel) {
-        for y in 0..FB_HEIGHT {
+        for y in 0..self.height {
             self.set_pixel(x, y, data);
         }
     }
 
     #[inline]
     fn fill(&mut self, data: &Self::Pixel) {
-        for pixel in self.chunks_exact_mut(8) {
+        for pixel in self.data.chunks_exact_mut(8) {
             pixel.copy_from_slice(data);
         }
     }
@@ -96,14 +131,28 @@ impl Default for Canvas {
 
 impl Canvas {
     pub fn new() -> Self {
+        Self::with_size(FB_WIDTH as u32, FB_HEIGHT as u32)
+    }
+
+    pub fn with_size(width: u32, height: u32) -> Self {
         Self {
-            frame: vec![0; BUF_LEN],
+            frame: Buffer::new(width as Size, height as Size),
         }
     }
 
     #[inline]
-    pub fn get_frame(&self) -> &Buffer {
-        &self.frame
+    pub fn width(&self) -> u32 {
+        self.frame.width as u32
+    }
+
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.frame.height as u32
+    }
+
+    #[inline]
+    pub fn get_frame(&self) -> &[u8] {
+        &self.frame.data
     }
 
     #[inline]
@@ -112,6 +161,190 @@ impl Canvas {
 
         frame.copy_from_slice(buf);
     }
+
+    fn colors(&self) -> impl Iterator<Item = Vec3> + '_ {
+        self.frame.data.chunks_exact(8).map(|pixel| {
+            let channel = |idx: usize| f16::from_le_bytes([pixel[idx], pixel[idx + 1]]).to_f32();
+
+            Vec3::new(channel(0), channel(2), channel(4))
+        })
+    }
+
+    /// Average log2 luminance of the lit pixels, clamped to the auto exposure range, as `ExposurePass` measures it
+    fn adapted_luminance(&self, settings: &PostSettings) -> f32 {
+        let (sum, lit) = self
+            .colors()
+            .map(luminance)
+            .filter(|&lum| lum > 0.0001)
+            .fold((0.0, 0), |(sum, lit), lum| (sum + lum.log2(), lit + 1));
+
+        if lit == 0 {
+            return 0.0;
+        }
+
+        (sum / lit as f32).clamp(settings.min_ev, settings.max_ev)
+    }
+
+    /// Tonemap to 8-bit sRGB with the same curve, exposure and gamma as `HDRPass`.
+    ///
+    /// Bloom and trails only exist on the GPU, and auto exposure is fully adapted to this frame alone
+    pub fn to_rgb8(&self, settings: &PostSettings) -> Vec<u8> {
+        let adapted = if settings.auto_exposure {
+            self.adapted_luminance(settings)
+        } else {
+            0.0
+        };
+
+        self.colors()
+            .flat_map(|hdr| {
+                let mapped = settings.tonemap(hdr, adapted);
+
+                [mapped.x, mapped.y, mapped.z].map(encode_srgb)
+            })
+            .collect()
+    }
+
+    /// Save a tonemapped 8-bit sRGB screenshot
+    pub fn save_png<P: AsRef<Path>>(
+        &self,
+        path: P,
+        settings: &PostSettings,
+    ) -> Result<(), ExportError> {
+        let path = path.as_ref();
+
+        let file = File::create(path).map_err(|source| ExportError::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width(), self.height());
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&self.to_rgb8(settings)))
+            .map_err(|source| ExportError::Png {
+                path: path.to_owned(),
+                source,
+            })
+    }
+
+    /// Save the raw linear half-float buffer, untouched by tonemapping
+    pub fn save_exr<P: AsRef<Path>>(&self, path: P) -> Result<(), ExportError> {
+        let path = path.as_ref();
+
+        exr::prelude::write_rgba_file(
+            path,
+            self.width() as usize,
+            self.height() as usize,
+            |x, y| {
+                let [r, g, b, a] = self.frame.get_pixel(x as Size, y as Size);
+                (r, g, b, a)
+            },
+        )
+        .map_err(|source| ExportError::Exr {
+            path: path.to_owned(),
+            source,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Png {
+        path: PathBuf,
+        source: png::EncodingError,
+    },
+    Exr {
+        path: PathBuf,
+        source: exr::error::Error,
+    },
+    Gif {
+        path: PathBuf,
+        source: gif::EncodingError,
+    },
+
+    /// A frame didn't match the size the recording was started with
+    FrameSize {
+        path: PathBuf,
+        expected: (u32, u32),
+        found: (u32, u32),
+    },
+}
+
+impl ExportError {
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Io { path, .. }
+            | Self::Png { path, .. }
+            | Self::Exr { path, .. }
+            | Self::Gif { path, .. }
+            | Self::FrameSize { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => {
+                write!(f, "failed to create {}: {}", path.display(), source)
+            }
+            Self::Png { path, source } => {
+                write!(f, "failed to write PNG {}: {}", path.display(), source)
+            }
+            Self::Exr { path, source } => {
+                write!(f, "failed to write EXR {}: {}", path.display(), source)
+            }
+            Self::Gif { path, source } => {
+                write!(f, "failed to write GIF {}: {}", path.display(), source)
+            }
+            Self::FrameSize {
+                path,
+                expected,
+                found,
+            } => write!(
+                f,
+                "can't add a {}x{} frame to {}, which is {}x{}",
+                found.0,
+                found.1,
+                path.display(),
+                expected.0,
+                expected.1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Png { source, .. } => Some(source),
+            Self::Exr { source, .. } => Some(source),
+            Self::Gif { source, .. } => Some(source),
+            Self::FrameSize { .. } => None,
+        }
+    }
+}
+
+/// Linear [0, 1] to an 8-bit sRGB value
+fn encode_srgb(linear: f32) -> u8 {
+    let linear = linear.clamp(0.0, 1.0);
+
+    let encoded = if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    };
+
+    (encoded * 255.0).round() as u8
 }
 
 impl Render for Canvas {
@@ -140,9 +373,7 @@ impl Render for Canvas {
 
     #[inline]
     fn circle(&mut self, (x, y): Point, radius: Size, color: Self::Color) {
-        let coords = BresenhamCircle::new(x, y, radius)
-            .into_iter()
-            .collect::<Vec<Point>>();
+        let coords = BresenhamCircle::new(x, y, radius).collect::<Vec<Point>>();
 
         let q1 = coords.iter().step_by(4).skip(1);
         let q2 = coords[1..].iter().step_by(4).skip(1).rev();
@@ -223,3 +454,24 @@ impl RgbaF16 {
         [rh, rl, gh, gl, bh, bl, ah, al]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canvases_take_any_size() {
+        let mut canvas = Canvas::with_size(7, 3);
+
+        assert_eq!((canvas.width(), canvas.height()), (7, 3));
+        assert_eq!(canvas.get_frame().len(), 7 * 3 * 8);
+
+        // the far corner is the last pixel in the buffer, not wrapped round to somewhere else
+        canvas.pixel((6, 2), RgbaF16::rgb(255, 255, 255));
+
+        let rgb = canvas.to_rgb8(&PostSettings::default());
+        assert_eq!(rgb.len(), 7 * 3 * 3);
+        assert!(rgb[..rgb.len() - 3].iter().all(|&c| c == 0));
+        assert!(rgb[rgb.len() - 3..].iter().all(|&c| c > 0));
+    }
+}
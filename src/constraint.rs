@@ -0,0 +1,204 @@
+// MIT License
+
+// Copyright (c) 2022 AnonmousDapper
+
+use serde::{Deserialize, Serialize};
+
+use crate::simulation::{Point, Velocity};
+
+/// A link between particles, naming them by `name`.
+///
+/// Lengths and anchors left out of a scene are taken from where the particles start.
+///
+/// ```toml
+/// [[constraint]]
+/// type = "rod"
+/// a = "bob"
+/// b = "pivot"
+///
+/// [[constraint]]
+/// type = "pin"
+/// particle = "pivot"
+/// ```
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Constraint {
+    /// Hookean spring, pulling or pushing the pair towards its rest length
+    Spring {
+        a: String,
+        b: String,
+
+        /// Force per unit of stretch
+        stiffness: f32,
+
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        rest_length: Option<f32>,
+
+        /// Resistance to the pair moving apart or together
+        #[serde(default)]
+        damping: f32,
+    },
+
+    /// Rigid rod, holding the pair exactly `length` apart
+    #[serde(alias = "distance")]
+    Rod {
+        a: String,
+        b: String,
+
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        length: Option<f32>,
+    },
+
+    /// Fixes a particle in place
+    #[serde(alias = "anchor")]
+    Pin {
+        particle: String,
+
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        position: Option<Point>,
+    },
+}
+
+impl Constraint {
+    pub fn spring(a: &str, b: &str, stiffness: f32, damping: f32) -> Self {
+        Self::Spring {
+            a: a.to_owned(),
+            b: b.to_owned(),
+            stiffness,
+            rest_length: None,
+            damping,
+        }
+    }
+
+    pub fn rod(a: &str, b: &str) -> Self {
+        Self::Rod {
+            a: a.to_owned(),
+            b: b.to_owned(),
+            length: None,
+        }
+    }
+
+    pub fn pin(particle: &str) -> Self {
+        Self::Pin {
+            particle: particle.to_owned(),
+            position: None,
+        }
+    }
+
+    /// Names of the particles involved; pins only have the first
+    pub fn particles(&self) -> [Option<&str>; 2] {
+        match self {
+            Self::Spring { a, b, .. } | Self::Rod { a, b, .. } => [Some(a), Some(b)],
+            Self::Pin { particle, .. } => [Some(particle), None],
+        }
+    }
+
+    /// Fill in a missing length or anchor, given where the named particles are and the distance between two points
+    pub fn settle<P, S>(&mut self, position: P, separation: S)
+    where
+        P: Fn(&str) -> Option<Point>,
+        S: Fn(Point, Point) -> Velocity,
+    {
+        let distance = |a: &str, b: &str| Some(separation(position(a)?, position(b)?).length());
+
+        match self {
+            Self::Spring {
+                a,
+                b,
+                rest_length: rest_length @ None,
+                ..
+            } => *rest_length = distance(a, b),
+
+            Self::Rod {
+                a,
+                b,
+                length: length @ None,
+            } => *length = distance(a, b),
+
+            Self::Pin {
+                particle,
+                position: anchor @ None,
+            } => *anchor = position(particle),
+
+            _ => {}
+        }
+    }
+
+    /// The constraint in terms of the indices of `particles()`, or `None` if one of them is gone
+    pub fn link(&self, indices: [Option<usize>; 2]) -> Option<Link> {
+        let [a, b] = indices;
+
+        Some(match *self {
+            Self::Spring {
+                stiffness,
+                rest_length,
+                damping,
+                ..
+            } => Link::Spring {
+                a: a?,
+                b: b?,
+                stiffness,
+                rest_length: rest_length?,
+                damping,
+            },
+
+            Self::Rod { length, .. } => Link::Rod {
+                a: a?,
+                b: b?,
+                length: length?,
+            },
+
+            Self::Pin { position, .. } => Link::Pin {
+                particle: a?,
+                position: position?,
+            },
+        })
+    }
+}
+
+/// A settled constraint between particles at known indices, for the duration of one step
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Link {
+    Spring {
+        a: usize,
+        b: usize,
+        stiffness: f32,
+        rest_length: f32,
+        damping: f32,
+    },
+
+    Rod {
+        a: usize,
+        b: usize,
+        length: f32,
+    },
+
+    Pin {
+        particle: usize,
+        position: Point,
+    },
+}
+
+/// Hookean force on a particle from another `a_to_b` away moving at `relative_velocity` to it,
+/// with damping along the line between them. The other particle feels the opposite
+pub fn spring_force(
+    stiffness: f32,
+    rest_length: f32,
+    damping: f32,
+    a_to_b: Velocity,
+    relative_velocity: Velocity,
+) -> Velocity {
+    let distance = a_to_b.length();
+
+    if distance == 0.0 {
+        return Velocity::ZERO;
+    }
+
+    let direction = a_to_b / distance;
+    let stretch = distance - rest_length;
+
+    direction * (stiffness * stretch + damping * relative_velocity.dot(direction))
+}
@@ -0,0 +1,638 @@
+// MIT License
+
+// Copyright (c) 2022 AnonmousDapper
+
+#![allow(non_upper_case_globals)]
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    constraint::spring_force,
+    quadtree::QuadTree,
+    simulation::{Boundary, ForceSolver, Particle, Point, Velocity},
+    soa::PointMasses,
+};
+
+/// Gravitational constant (m³/s²/kg)
+pub(crate) const G: f32 = 6.67384e-11;
+
+/// Coulomb constant (N·m²/C²)
+const k_e: f32 = 8.987_552e9;
+
+/// A particle as the forces see it partway through a step, which may not be where it was at the start
+#[derive(Clone, Copy, Debug)]
+pub struct Body<'a> {
+    pub particle: &'a Particle,
+    pub position: Point,
+    pub velocity: Velocity,
+}
+
+/// Everything a force gets to look at while working out accelerations
+#[derive(Clone, Copy, Debug)]
+pub struct System<'a> {
+    pub particles: &'a [Particle],
+    pub positions: &'a [Point],
+    pub velocities: &'a [Velocity],
+    pub boundary: Boundary,
+    pub world_size: Point,
+
+    /// Multiplier on G
+    pub physics_scale: f32,
+
+    /// Plummer softening length, for inverse-square forces
+    pub softening: f32,
+    pub solver: ForceSolver,
+
+    /// Barnes-Hut opening angle
+    pub theta: f32,
+
+    /// Positions and masses cached for the vectorised solver, already at `positions`, if whoever built the system keeps them.
+    /// The simulator does for `ForceSolver::Vectorised`
+    pub point_masses: Option<&'a PointMasses>,
+}
+
+impl<'a> System<'a> {
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    pub fn body(&self, idx: usize) -> Body<'a> {
+        Body {
+            particle: &self.particles[idx],
+            position: self.positions[idx],
+            velocity: self.velocities[idx],
+        }
+    }
+
+    /// Shortest displacement from `a` to `b`, across the edges of the world if it wraps
+    pub fn separation(&self, a: Point, b: Point) -> Velocity {
+        self.boundary.separation(a, b, self.world_size)
+    }
+}
+
+/// Something that accelerates particles, summed up by the `Simulator` every time it evaluates forces.
+///
+/// Most forces are easier to write as a `PairForce` or `FieldForce`, wrapped in `Pairwise` or `Field`
+pub trait Force: fmt::Debug {
+    /// Add the acceleration this force gives each particle to `accel`
+    fn accumulate(&self, system: &System<'_>, accel: &mut [Velocity]);
+
+    /// How to write this force in a scene. Forces without one are left out of snapshots
+    fn config(&self) -> Option<ForceConfig> {
+        None
+    }
+}
+
+/// A force between every pair of particles, equal and opposite
+pub trait PairForce: fmt::Debug {
+    /// Force on `a` due to `b`, `a_to_b` being the shortest separation between them
+    fn force(&self, a: &Body<'_>, b: &Body<'_>, a_to_b: Velocity, system: &System<'_>) -> Velocity;
+
+    fn config(&self) -> Option<ForceConfig> {
+        None
+    }
+}
+
+/// A force from outside the system, acting on each particle on its own
+pub trait FieldForce: fmt::Debug {
+    /// Force on `body`
+    fn force(&self, body: &Body<'_>, system: &System<'_>) -> Velocity;
+
+    fn config(&self) -> Option<ForceConfig> {
+        None
+    }
+}
+
+/// Runs a `PairForce` over every pair of particles, O(n²)
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Pairwise<F>(pub F);
+
+impl<F: PairForce> Force for Pairwise<F> {
+    fn accumulate(&self, system: &System<'_>, accel: &mut [Velocity]) {
+        for idx in 0..system.len() {
+            let a = system.body(idx);
+
+            for other in idx + 1..system.len() {
+                let b = system.body(other);
+                let force = self
+                    .0
+                    .force(&a, &b, system.separation(a.position, b.position), system);
+
+                accel[idx] += force / a.particle.mass();
+                accel[other] -= force / b.particle.mass();
+            }
+        }
+    }
+
+    fn config(&self) -> Option<ForceConfig> {
+        self.0.config()
+    }
+}
+
+/// Runs a `FieldForce` on every particle
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Field<F>(pub F);
+
+impl<F: FieldForce> Force for Field<F> {
+    fn accumulate(&self, system: &System<'_>, accel: &mut [Velocity]) {
+        for (idx, accel) in accel.iter_mut().enumerate() {
+            let body = system.body(idx);
+
+            *accel += self.0.force(&body, system) / body.particle.mass();
+        }
+    }
+
+    fn config(&self) -> Option<ForceConfig> {
+        self.0.config()
+    }
+}
+
+/// `a_to_b / |a_to_b|³`, with Plummer softening; zero when the two points coincide
+fn inverse_square(a_to_b: Velocity, softening: f32) -> Velocity {
+    let distance2 = a_to_b.length_squared() + softening * softening;
+
+    let falloff = (distance2 * distance2.sqrt()).recip();
+
+    // coincident (or close enough to underflow) with no softening, so there's no direction to push in
+    if !falloff.is_finite() {
+        return Velocity::ZERO;
+    }
+
+    a_to_b * falloff
+}
+
+/// Acceleration of a particle towards another `a_to_b` away, per unit mass of the latter.
+///
+/// `softening` is the Plummer length, which caps the pull between very close particles
+fn gravity(a_to_b: Velocity, scale: f32, softening: f32) -> Velocity {
+    inverse_square(a_to_b, softening) * (scale * G)
+}
+
+/// Newtonian gravity, summed directly, vectorised or with Barnes-Hut depending on the system's solver
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Gravity;
+
+impl Force for Gravity {
+    fn accumulate(&self, system: &System<'_>, accel: &mut [Velocity]) {
+        let pull = |a, b| {
+            gravity(
+                system.separation(a, b),
+                system.physics_scale,
+                system.softening,
+            )
+        };
+
+        match system.solver {
+            ForceSolver::Direct => {
+                for (idx, &pos_a) in system.positions.iter().enumerate() {
+                    let mass_a = system.particles[idx].mass();
+
+                    for (offset, &pos_b) in system.positions[idx + 1..].iter().enumerate() {
+                        let other = idx + 1 + offset;
+                        let pull = pull(pos_a, pos_b);
+
+                        accel[idx] += pull * system.particles[other].mass();
+                        accel[other] -= pull * mass_a;
+                    }
+                }
+            }
+
+            ForceSolver::BarnesHut => {
+                let masses: Vec<f32> = system.particles.iter().map(Particle::mass).collect();
+                let tree = QuadTree::new(system.positions, &masses);
+
+                for (idx, accel) in accel.iter_mut().enumerate() {
+                    *accel +=
+                        tree.acceleration(idx, system.theta, |a, b| system.separation(a, b), pull);
+                }
+            }
+
+            ForceSolver::Vectorised => {
+                let built;
+
+                let point_masses = match system.point_masses {
+                    Some(point_masses) => point_masses,
+                    None => {
+                        let mut point_masses = PointMasses::from_particles(system.particles);
+                        point_masses.set_positions(system.positions);
+
+                        built = point_masses;
+                        &built
+                    }
+                };
+
+                let wrap = (system.boundary == Boundary::Torus).then_some(system.world_size);
+
+                point_masses.gravity(system.physics_scale * G, system.softening, wrap, accel);
+            }
+        }
+    }
+
+    fn config(&self) -> Option<ForceConfig> {
+        Some(ForceConfig::Gravity)
+    }
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+/// Electrostatic force between charges. Charges can cancel out, so this is always summed directly
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Coulomb {
+    /// Multiplier on the Coulomb constant
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+}
+
+impl Default for Coulomb {
+    fn default() -> Self {
+        Self {
+            scale: default_scale(),
+        }
+    }
+}
+
+impl PairForce for Coulomb {
+    fn force(&self, a: &Body<'_>, b: &Body<'_>, a_to_b: Velocity, system: &System<'_>) -> Velocity {
+        let product = a.particle.charge() * b.particle.charge();
+
+        if product == 0.0 {
+            return Velocity::ZERO;
+        }
+
+        // like charges push apart
+        -inverse_square(a_to_b, system.softening) * (self.scale * k_e * product)
+    }
+
+    fn config(&self) -> Option<ForceConfig> {
+        Some(ForceConfig::Coulomb(*self))
+    }
+}
+
+fn default_cutoff() -> f32 {
+    2.5
+}
+
+/// 12-6 Lennard-Jones: strong repulsion up close, weak attraction further out.
+///
+/// Pairs further apart than `cutoff` σ are skipped
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct LennardJones {
+    /// Depth of the potential well
+    pub epsilon: f32,
+
+    /// Distance at which the potential is zero; the well bottoms out at 2^(1/6) σ
+    pub sigma: f32,
+
+    #[serde(default = "default_cutoff")]
+    pub cutoff: f32,
+}
+
+impl PairForce for LennardJones {
+    fn force(
+        &self,
+        _a: &Body<'_>,
+        _b: &Body<'_>,
+        a_to_b: Velocity,
+        system: &System<'_>,
+    ) -> Velocity {
+        let distance2 = a_to_b.length_squared() + system.softening * system.softening;
+
+        if distance2 > (self.cutoff * self.sigma).powi(2) || distance2 == 0.0 {
+            return Velocity::ZERO;
+        }
+
+        let ratio6 = (self.sigma * self.sigma / distance2).powi(3);
+
+        // -dV/dr / r, so positive pushes `a` away from `b`
+        let push = 24.0 * self.epsilon * (2.0 * ratio6 * ratio6 - ratio6) / distance2;
+
+        -a_to_b * push
+    }
+
+    fn config(&self) -> Option<ForceConfig> {
+        Some(ForceConfig::LennardJones(*self))
+    }
+}
+
+/// Hooke's-law bonds between every pair of particles closer than `range`, for soft bodies and lattices
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Spring {
+    /// Force per unit of stretch
+    pub stiffness: f32,
+
+    /// Length at which a bond neither pulls nor pushes
+    pub rest_length: f32,
+
+    /// Bonds only hold between particles this close
+    pub range: f32,
+
+    /// Resistance to the pair moving apart or together
+    #[serde(default)]
+    pub damping: f32,
+}
+
+impl PairForce for Spring {
+    fn force(
+        &self,
+        a: &Body<'_>,
+        b: &Body<'_>,
+        a_to_b: Velocity,
+        _system: &System<'_>,
+    ) -> Velocity {
+        if a_to_b.length() > self.range {
+            return Velocity::ZERO;
+        }
+
+        spring_force(
+            self.stiffness,
+            self.rest_length,
+            self.damping,
+            a_to_b,
+            b.velocity - a.velocity,
+        )
+    }
+
+    fn config(&self) -> Option<ForceConfig> {
+        Some(ForceConfig::Spring(*self))
+    }
+}
+
+/// Linear drag against a still medium, slowing everything in proportion to its speed
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Drag {
+    pub coefficient: f32,
+}
+
+impl FieldForce for Drag {
+    fn force(&self, body: &Body<'_>, _system: &System<'_>) -> Velocity {
+        -body.velocity * self.coefficient
+    }
+
+    fn config(&self) -> Option<ForceConfig> {
+        Some(ForceConfig::Drag(*self))
+    }
+}
+
+/// The same field everywhere: a constant acceleration, and an electric field pushing on charges
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct UniformField {
+    /// Acceleration given to everything, regardless of mass
+    #[serde(default)]
+    pub gravity: Velocity,
+
+    /// Electric field (N/C)
+    #[serde(default)]
+    pub electric: Velocity,
+}
+
+impl FieldForce for UniformField {
+    fn force(&self, body: &Body<'_>, _system: &System<'_>) -> Velocity {
+        self.gravity * body.particle.mass() + self.electric * body.particle.charge()
+    }
+
+    fn config(&self) -> Option<ForceConfig> {
+        Some(ForceConfig::UniformField(*self))
+    }
+}
+
+/// The built-in forces, as written in scene files.
+///
+/// ```toml
+/// [[force]]
+/// type = "gravity"
+///
+/// [[force]]
+/// type = "lennard_jones"
+/// epsilon = 0.5
+/// sigma = 4.0
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ForceConfig {
+    Gravity,
+
+    #[serde(alias = "electrostatic")]
+    Coulomb(Coulomb),
+
+    LennardJones(LennardJones),
+
+    #[serde(alias = "bond")]
+    Spring(Spring),
+
+    Drag(Drag),
+
+    UniformField(UniformField),
+}
+
+impl ForceConfig {
+    pub fn build(self) -> Box<dyn Force> {
+        match self {
+            Self::Gravity => Box::new(Gravity),
+            Self::Coulomb(force) => Box::new(Pairwise(force)),
+            Self::LennardJones(force) => Box::new(Pairwise(force)),
+            Self::Spring(force) => Box::new(Pairwise(force)),
+            Self::Drag(force) => Box::new(Field(force)),
+            Self::UniformField(force) => Box::new(Field(force)),
+        }
+    }
+}
+
+pub fn default_forces() -> Vec<ForceConfig> {
+    vec![ForceConfig::Gravity]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coincident_pull_is_zero() {
+        let point = Point::new(5.0, 5.0);
+
+        assert_eq!(gravity(point - point, 1.0, 0.0), Velocity::ZERO);
+        assert_eq!(gravity(point - point, 1.0, 0.5), Velocity::ZERO);
+    }
+
+    #[test]
+    fn near_coincident_pull_is_finite() {
+        assert!(gravity(Point::new(1e-30, 0.0), 1.0, 0.0).is_finite());
+    }
+
+    #[test]
+    fn softening_bounds_close_pull() {
+        let softening = 0.5;
+
+        // Plummer acceleration peaks at r = ε/√2
+        let peak = 2.0 * G / (3.0 * 3f32.sqrt() * softening * softening);
+
+        for distance in [1e-6, 1e-3, 0.1, softening / 2f32.sqrt(), 1.0] {
+            let pull = gravity(Point::new(distance, 0.0), 1.0, softening);
+
+            assert!(pull.length() <= peak * 1.0001);
+        }
+    }
+
+    #[test]
+    fn vectorised_gravity_matches_direct() {
+        let particles: Vec<Particle> = (0..37)
+            .map(|idx| {
+                let angle = idx as f32 * 2.4;
+                let position = Point::new(128.0, 128.0)
+                    + Point::new(angle.cos(), angle.sin()) * (idx as f32 * 3.0);
+
+                Particle::new(
+                    position,
+                    Velocity::ZERO,
+                    (0, 0, 0),
+                    0.0,
+                    1.0,
+                    1e9 + idx as f32 * 1e8,
+                    0.0,
+                )
+            })
+            .collect();
+
+        let positions: Vec<Point> = particles.iter().map(Particle::position).collect();
+        let velocities = vec![Velocity::ZERO; particles.len()];
+
+        for boundary in [Boundary::Open, Boundary::Torus] {
+            let accelerations = |solver| {
+                let system = System {
+                    particles: &particles,
+                    positions: &positions,
+                    velocities: &velocities,
+                    boundary,
+                    world_size: Point::splat(256.0),
+                    physics_scale: 1.0,
+                    softening: 0.5,
+                    solver,
+                    theta: 0.5,
+                    point_masses: None,
+                };
+
+                let mut accel = vec![Velocity::ZERO; particles.len()];
+                Gravity.accumulate(&system, &mut accel);
+
+                accel
+            };
+
+            let direct = accelerations(ForceSolver::Direct);
+            let vectorised = accelerations(ForceSolver::Vectorised);
+
+            for (exact, fast) in direct.iter().zip(&vectorised) {
+                assert!((*exact - *fast).length() <= exact.length() * 1e-4 + 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn barnes_hut_pulls_across_the_seam() {
+        // a tight cluster straddling the right-hand edge of a wrapping world, plus a probe just across it
+        let mut positions: Vec<Point> = (0..16)
+            .map(|idx| Point::new((250.0 + idx as f32 * 0.6) % 256.0, 128.0 + (idx % 4) as f32))
+            .collect();
+        positions.push(Point::new(8.0, 129.0));
+
+        // and a few bodies elsewhere, so the tree has some depth to it
+        positions.extend(
+            (0..8).map(|idx| Point::new(60.0 + idx as f32 * 20.0, 40.0 + idx as f32 * 7.0)),
+        );
+
+        let particles: Vec<Particle> = positions
+            .iter()
+            .map(|&position| Particle::new(position, Velocity::ZERO, (0, 0, 0), 0.0, 1.0, 1e9, 0.0))
+            .collect();
+        let velocities = vec![Velocity::ZERO; particles.len()];
+
+        let accelerations = |solver| {
+            let system = System {
+                particles: &particles,
+                positions: &positions,
+                velocities: &velocities,
+                boundary: Boundary::Torus,
+                world_size: Point::splat(256.0),
+                physics_scale: 1.0,
+                softening: 0.0,
+                solver,
+                theta: 0.5,
+                point_masses: None,
+            };
+
+            let mut accel = vec![Velocity::ZERO; particles.len()];
+            Gravity.accumulate(&system, &mut accel);
+
+            accel
+        };
+
+        let direct = accelerations(ForceSolver::Direct);
+        let tree = accelerations(ForceSolver::BarnesHut);
+
+        // the cluster and the probe, which are where reading the tree without the wrap goes wrong
+        for (exact, approx) in direct.iter().zip(&tree).take(17) {
+            assert!((*exact - *approx).length() <= exact.length() * 0.02);
+        }
+
+        // the probe is pulled back across the seam towards the cluster
+        assert!(tree[16].x < 0.0);
+    }
+
+    #[test]
+    fn lennard_jones_settles_at_the_well() {
+        let force = LennardJones {
+            epsilon: 1.0,
+            sigma: 1.0,
+            cutoff: default_cutoff(),
+        };
+
+        let particle = Particle::new(Point::ZERO, Velocity::ZERO, (0, 0, 0), 0.0, 1.0, 1.0, 0.0);
+        let particles = [particle.clone(), particle];
+        let system = System {
+            particles: &particles,
+            positions: &[Point::ZERO, Point::ZERO],
+            velocities: &[Velocity::ZERO, Velocity::ZERO],
+            boundary: Boundary::Open,
+            world_size: Point::ONE,
+            physics_scale: 1.0,
+            softening: 0.0,
+            solver: ForceSolver::Direct,
+            theta: 0.5,
+            point_masses: None,
+        };
+
+        let (a, b) = (system.body(0), system.body(1));
+        let well = 2f32.powf(1.0 / 6.0);
+
+        let push = |distance: f32| force.force(&a, &b, Velocity::new(distance, 0.0), &system).x;
+
+        assert!(push(well * 0.9) < 0.0);
+        assert!(push(well).abs() < 1e-4);
+        assert!(push(well * 1.1) > 0.0);
+        assert_eq!(push(3.0), 0.0);
+    }
+
+    #[test]
+    fn scene_forces_round_trip() {
+        let forces = vec![
+            ForceConfig::Gravity,
+            ForceConfig::Coulomb(Coulomb { scale: 1e-4 }),
+            ForceConfig::Drag(Drag { coefficient: 0.1 }),
+        ];
+
+        for config in &forces {
+            assert_eq!(config.build().config().as_ref(), Some(config));
+        }
+    }
+}
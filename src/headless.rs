@@ -0,0 +1,68 @@
+// MIT License
+
+// Copyright (c) 2022 AnonmousDapper
+
+use std::path::Path;
+
+use crate::{
+    canvas::{Canvas, ExportError, Render},
+    pipeline::PostSettings,
+    recorder::{RecordFormat, Recorder},
+    simulation::Simulator,
+};
+
+/// Steps a simulator without a window, recording a fixed number of frames.
+///
+/// Frames are drawn on the CPU canvas and tonemapped like `HDRPass`, but the bloom and trail
+/// passes only exist on the GPU, so they don't show up here.
+pub struct Headless {
+    canvas: Canvas,
+    recorder: Recorder,
+    settings: PostSettings,
+}
+
+impl Headless {
+    /// Size the canvas to the simulator's resolution and start recording to `out`, tonemapped with `settings`
+    pub fn new<P: AsRef<Path>>(
+        sim: &Simulator,
+        settings: PostSettings,
+        out: P,
+        format: RecordFormat,
+        frames: u32,
+    ) -> Result<Self, ExportError> {
+        let (width, height) = sim.resolution();
+
+        Ok(Self {
+            canvas: Canvas::with_size(width, height),
+            recorder: Recorder::new(out, format, width, height)?.with_limit(frames),
+            settings,
+        })
+    }
+
+    /// Take exactly one step of the scene's timestep before each frame until the limit is reached,
+    /// so the same scene always renders the same frames whatever the machine
+    pub fn run(mut self, sim: &mut Simulator) -> Result<(), ExportError> {
+        while !self.recorder.is_full() {
+            sim.step_physics(sim.timestep());
+            self.render(sim)?;
+        }
+
+        self.recorder.finish()
+    }
+
+    /// Draw the current state and record it, returning false once the limit is reached
+    pub fn render(&mut self, sim: &Simulator) -> Result<bool, ExportError> {
+        self.canvas.clear();
+        sim.render(&mut self.canvas);
+
+        self.recorder.record(&self.canvas, &self.settings)
+    }
+
+    pub fn canvas(&self) -> &Canvas {
+        &self.canvas
+    }
+
+    pub fn recorder(&self) -> &Recorder {
+        &self.recorder
+    }
+}
@@ -14,10 +14,26 @@ pub const WINDOW_HEIGHT: u32 = 700;
 pub const PIPELINE_TEXTURE_FORMAT: pixels::wgpu::TextureFormat =
     pixels::wgpu::TextureFormat::Rgba16Float;
 
+pub mod camera;
+
 pub mod canvas;
 
 pub mod window;
 
+pub mod headless;
+
+pub mod recorder;
+
 pub mod pipeline;
 
+pub mod quadtree;
+
+pub mod scene;
+
 pub mod simulation;
+
+pub mod force;
+
+pub mod constraint;
+
+pub mod soa;
@@ -4,6 +4,12 @@
 
 #![deny(rust_2018_idioms)]
 
+use std::{
+    ffi::OsString,
+    path::PathBuf,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
 use pixels::{PixelsBuilder, SurfaceTexture};
 
 use winit::{
@@ -13,11 +19,21 @@ use winit::{
 
 use winit_input_helper::WinitInputHelper;
 
-use log::error;
+use log::{debug, error, info};
 
 use pixie::canvas::{Canvas, Render};
 
-use pixie::simulation::{Particle, Point, Simulator, Velocity};
+use pixie::camera::Camera;
+
+use pixie::headless::Headless;
+
+use pixie::recorder::{RecordFormat, Recorder};
+
+use pixie::pipeline::{BloomMode, PassKind, PostSettings, RenderConfig, ShaderPipeline};
+
+use pixie::scene::Scene;
+
+use pixie::simulation::{ColorModel, Point, Simulator, ThermalSettings};
 
 // =======================================
 //
@@ -30,22 +46,186 @@ use pixie::simulation::{Particle, Point, Simulator, Velocity};
 //
 // =======================================
 
-fn rgb(r: u8, g: u8, b: u8) -> (u8, u8, u8) {
-    (r, g, b)
+/// The scene run when none is given, shared with scenes/default.toml so the two can't drift apart
+const DEFAULT_SCENE: &str = include_str!("../scenes/default.toml");
+
+fn default_scene() -> Result<Scene, toml::de::Error> {
+    DEFAULT_SCENE.parse()
+}
+
+fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default()
+}
+
+/// Save the simulation along with the post-processing it is being shown with
+fn save_snapshot(sim: &Simulator, render: RenderConfig) {
+    let path = format!("snapshot-{}.toml", timestamp());
+
+    let mut scene = sim.snapshot();
+    scene.set_render_config(render);
+
+    match scene.save(&path) {
+        Ok(()) => info!("saved snapshot to {}", path),
+        Err(e) => error!("{}", e),
+    }
+}
+
+/// Command line: `pixie [--size WxH] [--format gif|png|y4m] [--frames N [--out PATH]] [scene]`
+#[derive(Debug, Default)]
+struct Options {
+    scene: Option<PathBuf>,
+    size: Option<(u32, u32)>,
+
+    /// Render this many frames without a window, then exit
+    frames: Option<u32>,
+
+    /// Where headless frames go
+    out: Option<PathBuf>,
+
+    /// Encoding for recordings, headless or not
+    format: Option<RecordFormat>,
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = OsString>>(mut args: I) -> Result<Self, String> {
+        let mut options = Self::default();
+
+        while let Some(arg) = args.next() {
+            if arg == "--size" {
+                let value = args.next().ok_or("--size needs a value, like 512x512")?;
+                options.size = Some(parse_size(&value.to_string_lossy())?);
+            } else if arg == "--frames" {
+                let value = args.next().ok_or("--frames needs a count")?;
+                let value = value.to_string_lossy();
+
+                options.frames = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("invalid frame count {:?}", value))?,
+                );
+            } else if arg == "--out" {
+                options.out = Some(args.next().ok_or("--out needs a path")?.into());
+            } else if arg == "--format" {
+                let value = args.next().ok_or("--format needs one of gif, png or y4m")?;
+                options.format = Some(value.to_string_lossy().parse()?);
+            } else if options.scene.is_none() {
+                options.scene = Some(arg.into());
+            } else {
+                return Err(format!("unexpected argument {:?}", arg));
+            }
+        }
+
+        if options.out.is_some() && options.frames.is_none() {
+            return Err("--out only applies to headless runs, add --frames".to_owned());
+        }
+
+        Ok(options)
+    }
+}
+
+fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("invalid size {:?}, expected WIDTHxHEIGHT", value);
+
+    let (width, height) = value.split_once(['x', 'X']).ok_or_else(invalid)?;
+    let width = width.parse().map_err(|_| invalid())?;
+    let height = height.parse().map_err(|_| invalid())?;
+
+    if width == 0 || height == 0 {
+        return Err(invalid());
+    }
+
+    Ok((width, height))
+}
+
+/// Save the last drawn frame, as linear EXR when `hdr` is set or tonemapped PNG otherwise
+fn save_screenshot(canvas: &Canvas, settings: &PostSettings, hdr: bool) {
+    let path = format!(
+        "screenshot-{}.{}",
+        timestamp(),
+        if hdr { "exr" } else { "png" }
+    );
+
+    let result = if hdr {
+        canvas.save_exr(&path)
+    } else {
+        canvas.save_png(&path, settings)
+    };
+
+    match result {
+        Ok(()) => info!("saved screenshot to {}", path),
+        Err(e) => error!("{}", e),
+    }
+}
+
+/// Start a recording of the canvas, or finish the one in progress
+fn toggle_recording(recorder: &mut Option<Recorder>, format: RecordFormat, canvas: &Canvas) {
+    let result = match recorder.take() {
+        Some(recorder) => recorder.finish(),
+        None => {
+            let path = match format {
+                RecordFormat::Png => format!("recording-{}", timestamp()),
+                _ => format!("recording-{}.{}", timestamp(), format.extension()),
+            };
+
+            Recorder::new(&path, format, canvas.width(), canvas.height()).map(|started| {
+                info!("recording to {}", path);
+                *recorder = Some(started);
+            })
+        }
+    };
+
+    if let Err(e) = result {
+        error!("{}", e);
+    }
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init();
 
+    let options = Options::parse(std::env::args_os().skip(1))?;
+
+    let scene = match &options.scene {
+        Some(path) => Scene::load(path)?,
+        None => default_scene()?,
+    };
+
+    // the renderer owns the post-processing from here on
+    let render = scene.render_config();
+    let mut sim = Simulator::from_scene(scene);
+
+    // the command line wins over whatever the scene asked for
+    if let Some((width, height)) = options.size {
+        sim.set_resolution(width, height);
+    }
+
+    if let Some(frames) = options.frames {
+        let format = options.format.unwrap_or(RecordFormat::Png);
+
+        let out = options.out.unwrap_or_else(|| match format {
+            RecordFormat::Png => PathBuf::from("frames"),
+            _ => PathBuf::from(format!("frames.{}", format.extension())),
+        });
+
+        return Ok(Headless::new(&sim, render.settings, out, format, frames)?.run(&mut sim)?);
+    }
+
+    let record_format = options.format.unwrap_or(RecordFormat::Gif);
+    let mut recorder: Option<Recorder> = None;
+
+    let (canvas_width, canvas_height) = sim.resolution();
+
     let event_loop = EventLoop::new();
     let mut input = WinitInputHelper::new();
 
     let (window, window_width, window_height, _hipdi) =
-        pixie::window::create_window("P.I.X.I.E.", &event_loop)?;
+        pixie::window::create_window("P.I.X.I.E.", &event_loop, canvas_width, canvas_height)?;
 
     let mut pixels = {
         let surtex = SurfaceTexture::new(window_width, window_height, &window);
-        PixelsBuilder::new(pixie::FB_WIDTH as u32, pixie::FB_HEIGHT as u32, surtex)
+        PixelsBuilder::new(canvas_width, canvas_height, surtex)
             //.wgpu_backend(pixels::wgpu::Backends::VULKAN)
             .texture_format(pixie::PIPELINE_TEXTURE_FORMAT)
             .render_texture_format(pixie::PIPELINE_TEXTURE_FORMAT)
@@ -53,60 +233,31 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             .build()?
     };
 
-    let mut shader = pixie::pipeline::ShaderPipeline::new(
-        &pixels,
-        pixie::FB_WIDTH as u32,
-        pixie::FB_HEIGHT as u32,
-    );
+    let mut shader = ShaderPipeline::with_config(&pixels, canvas_width, canvas_height, &render);
 
-    let mut sim = Simulator::new();
-    let mut canvas = Canvas::new();
-
-    sim.add_particle(
-        Particle::new(
-            Point::new(80., 80.),
-            Velocity::new(0., 0.02),
-            rgb(255, 202, 40),
-            400.,
-            11.,
-            7e9,
-            0.,
-        )
-        .with_name("Sol"),
-    );
-
-    sim.add_particle(
-        Particle::new(
-            Point::new(80., 90.),
-            Velocity::new(-0.1, 0.0),
-            rgb(105, 240, 174),
-            1700.,
-            7.,
-            7e7,
-            0.,
-        )
-        .with_name("green blob"),
-    );
+    let mut canvas = Canvas::with_size(canvas_width, canvas_height);
 
-    sim.add_particle(
-        Particle::new(
-            Point::new(10., 28.),
-            Velocity::new(0.2, 0.05),
-            rgb(25, 118, 210),
-            0.,
-            0.,
-            7e3,
-            0.,
-        )
-        .with_name("blue"),
-    );
+    let mut last_frame = Instant::now();
+    let mut last_redraw = Instant::now();
 
     event_loop.run(move |evt, _, flow| {
         if let Event::RedrawRequested(_) = evt {
+            let now = Instant::now();
+            shader.advance(now - last_redraw);
+            last_redraw = now;
+
             canvas.clear();
             sim.render(&mut canvas);
             canvas.render_to(pixels.get_frame());
 
+            if let Some(Err(e)) = recorder
+                .as_mut()
+                .map(|recorder| recorder.record(&canvas, shader.settings()))
+            {
+                error!("{}", e);
+                recorder = None;
+            }
+
             //let result = pixels.render();
 
             let result = pixels.render_with(|encoder, target, ctx| {
@@ -141,12 +292,179 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 *flow = ControlFlow::Exit;
             }
 
+            if input.key_pressed(VirtualKeyCode::F5) {
+                save_snapshot(&sim, shader.config());
+            }
+
+            if input.key_pressed(VirtualKeyCode::R) {
+                toggle_recording(&mut recorder, record_format, &canvas);
+            }
+
+            let mut chain_changed = false;
+
+            if input.key_pressed(VirtualKeyCode::B) {
+                shader.toggle(PassKind::Bloom);
+                chain_changed = true;
+            }
+
+            if input.key_pressed(VirtualKeyCode::T) {
+                shader.toggle(PassKind::Trail);
+                chain_changed = true;
+            }
+
+            // rotate the chain, so the first pass runs last
+            if input.key_pressed(VirtualKeyCode::O) {
+                shader.move_pass(0, shader.passes().len().saturating_sub(1));
+                chain_changed = true;
+            }
+
+            if chain_changed {
+                info!("post chain {:?}", shader.passes());
+            }
+
+            if input.key_pressed(VirtualKeyCode::F12) {
+                save_screenshot(&canvas, shader.settings(), input.held_shift());
+            }
+
             if let Some(size) = input.window_resized() {
                 pixels.resize_surface(size.width, size.height);
                 shader.resize(&pixels, size.width, size.height);
             }
 
-            sim.step_physics();
+            if input.key_pressed(VirtualKeyCode::Space) {
+                sim.toggle_pause();
+            }
+
+            if input.key_pressed(VirtualKeyCode::Period) {
+                sim.single_step();
+            }
+
+            if input.key_pressed(VirtualKeyCode::Equals)
+                || input.key_pressed(VirtualKeyCode::NumpadAdd)
+            {
+                sim.speed_up();
+                info!("time scale {}x", sim.time_scale());
+            }
+
+            if input.key_pressed(VirtualKeyCode::Minus)
+                || input.key_pressed(VirtualKeyCode::NumpadSubtract)
+            {
+                sim.slow_down();
+                info!("time scale {}x", sim.time_scale());
+            }
+
+            let axis = |negative, positive| {
+                input.key_held(positive) as u8 as f32 - input.key_held(negative) as u8 as f32
+            };
+
+            let pan = Point::new(
+                axis(VirtualKeyCode::Left, VirtualKeyCode::Right),
+                axis(VirtualKeyCode::Up, VirtualKeyCode::Down),
+            );
+
+            if pan != Point::ZERO {
+                let camera = sim.camera_mut();
+                camera.unfollow();
+                camera.pan(pan * 2.0);
+            }
+
+            if input.key_pressed(VirtualKeyCode::M) {
+                let tonemapper = shader.tonemapper().next();
+
+                shader.set_tonemapper(&pixels, tonemapper);
+                info!("tonemapper {:?}", tonemapper);
+            }
+
+            if input.key_pressed(VirtualKeyCode::E) {
+                let settings = PostSettings {
+                    auto_exposure: !shader.settings().auto_exposure,
+                    ..*shader.settings()
+                };
+
+                shader.set_settings(&pixels, settings);
+                info!(
+                    "auto exposure {}",
+                    if settings.auto_exposure { "on" } else { "off" }
+                );
+            }
+
+            if input.key_pressed(VirtualKeyCode::H) {
+                let thermal = ThermalSettings {
+                    enabled: !sim.thermal().enabled,
+                    ..*sim.thermal()
+                };
+
+                sim.set_thermal(thermal);
+                info!(
+                    "thermal simulation {}",
+                    if thermal.enabled { "on" } else { "off" }
+                );
+            }
+
+            if input.key_pressed(VirtualKeyCode::C) {
+                let model = match sim.color_model() {
+                    ColorModel::Authored => ColorModel::Blackbody,
+                    ColorModel::Blackbody => ColorModel::Blend,
+                    ColorModel::Blend => ColorModel::Authored,
+                };
+
+                sim.set_color_model(model);
+                info!("color model {:?}", model);
+            }
+
+            if input.key_pressed(VirtualKeyCode::G) {
+                let settings = PostSettings {
+                    bloom_mode: match shader.settings().bloom_mode {
+                        BloomMode::Gaussian => BloomMode::DualFilter,
+                        BloomMode::DualFilter => BloomMode::Gaussian,
+                    },
+                    ..*shader.settings()
+                };
+
+                shader.set_settings(&pixels, settings);
+                info!("bloom mode {:?}", settings.bloom_mode);
+            }
+
+            let exposure = axis(VirtualKeyCode::LBracket, VirtualKeyCode::RBracket);
+
+            if exposure != 0.0 {
+                let mut settings = *shader.settings();
+                settings.exposure *= 1.01f32.powf(exposure);
+
+                shader.set_settings(&pixels, settings);
+            }
+
+            if input.key_pressed(VirtualKeyCode::Z) {
+                sim.camera_mut().zoom_by(1.25);
+            }
+
+            if input.key_pressed(VirtualKeyCode::X) {
+                sim.camera_mut().zoom_by(0.8);
+            }
+
+            if input.key_pressed(VirtualKeyCode::Tab) {
+                sim.follow_next();
+            }
+
+            if input.key_pressed(VirtualKeyCode::Back) {
+                sim.camera_mut().unfollow();
+            }
+
+            if input.key_pressed(VirtualKeyCode::Home) {
+                *sim.camera_mut() = Camera::fit(sim.world_size(), sim.camera().viewport());
+            }
+
+            let now = Instant::now();
+            sim.advance(now - last_frame);
+            last_frame = now;
+
+            for collision in sim.drain_collisions() {
+                debug!(
+                    "{:?}: {} - {} ({} J)",
+                    collision.outcome, collision.names[0], collision.names[1], collision.energy
+                );
+            }
+
             window.request_redraw();
         }
     });
@@ -5,15 +5,22 @@
 use pixels::wgpu::util::{BufferInitDescriptor, DeviceExt};
 use pixels::wgpu::{
     include_wgsl, AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
-    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendState,
-    BufferSize, BufferUsages, Color, ColorTargetState, ColorWrites, CommandEncoder, Device,
-    Extent3d, FilterMode, FragmentState, LoadOp, MultisampleState, Operations,
-    PipelineLayoutDescriptor, PrimitiveState, RenderPassColorAttachment, RenderPassDescriptor,
-    RenderPipeline, RenderPipelineDescriptor, Sampler, SamplerBindingType, SamplerDescriptor,
-    ShaderStages, TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType,
-    TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension, VertexState,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendComponent,
+    BlendFactor, BlendOperation, BlendState, Buffer, BufferSize, BufferUsages, Color,
+    ColorTargetState, ColorWrites, CommandEncoder, Device, Extent3d, FilterMode, FragmentState,
+    LoadOp, MultisampleState, Operations, PipelineLayoutDescriptor, PrimitiveState, Queue,
+    RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor,
+    Sampler, SamplerBindingType, SamplerDescriptor, ShaderModule, ShaderStages, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureView,
+    TextureViewDescriptor, TextureViewDimension, VertexState,
 };
 
+use std::time::Duration;
+
+use glam::{Mat3, Vec3};
+
+use serde::{Deserialize, Serialize};
+
 use crate::PIPELINE_TEXTURE_FORMAT;
 
 fn create_texture_view(
@@ -51,40 +58,515 @@ fn create_sampler(device: &Device) -> Sampler {
     })
 }
 
+/// A post-processing stage run on the HDR pipeline texture, in place: it reads the
+/// texture it was created with and writes its result back into it
+pub trait PostPass {
+    fn kind(&self) -> PassKind;
+
+    fn resize(&mut self, device: &Device, input: &TextureView, width: u32, height: u32);
+
+    fn render(
+        &self,
+        encoder: &mut CommandEncoder,
+        target: &TextureView,
+        clip: (u32, u32, u32, u32),
+        uniforms: &PostUniforms,
+    );
+}
+
+/// The post-processing passes that can go in a chain
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PassKind {
+    Bloom,
+    Trail,
+}
+
+impl PassKind {
+    fn create(
+        self,
+        device: &Device,
+        input: &TextureView,
+        width: u32,
+        height: u32,
+        vertex: VertexState<'_>,
+        uniforms: &PostUniforms,
+    ) -> Box<dyn PostPass> {
+        match self {
+            Self::Bloom => Box::new(BloomPass::new(
+                device, input, width, height, vertex, uniforms,
+            )),
+            Self::Trail => Box::new(TrailPass::new(
+                device, input, width, height, vertex, uniforms,
+            )),
+        }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// One entry in the post-processing chain, as written in scenes
+///
+/// ```toml
+/// [[post]]
+/// pass = "trail"
+///
+/// [[post]]
+/// pass = "bloom"
+/// enabled = false
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PassConfig {
+    #[serde(rename = "pass")]
+    pub kind: PassKind,
+
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+impl PassConfig {
+    pub fn new(kind: PassKind) -> Self {
+        Self {
+            kind,
+            enabled: true,
+        }
+    }
+}
+
+/// The original fixed chain: bloom, then trails
+pub fn default_post_passes() -> Vec<PassConfig> {
+    vec![
+        PassConfig::new(PassKind::Bloom),
+        PassConfig::new(PassKind::Trail),
+    ]
+}
+
+/// How a scene is post-processed; kept by whatever renders it, not by the simulator
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderConfig {
+    /// Post-processing chain, run in order
+    pub passes: Vec<PassConfig>,
+
+    pub settings: PostSettings,
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            passes: default_post_passes(),
+            settings: PostSettings::default(),
+        }
+    }
+}
+
+/// How `BloomPass` spreads bright pixels out
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BloomMode {
+    /// Separable blur at full resolution, `blur_iterations` times over
+    #[default]
+    Gaussian,
+
+    /// Downsample and upsample through `bloom_radius` half-size levels, for wide glows
+    DualFilter,
+}
+
+/// How `HDRPass` squeezes HDR values into displayable range
+///
+/// The discriminants are what `hdr.wgsl` switches on
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tonemapper {
+    /// `1 - e^-x`
+    #[default]
+    Exposure,
+
+    Reinhard,
+
+    /// Reinhard on luminance, reaching white at `white_point`
+    ExtendedReinhard,
+
+    /// Narkowicz's fit of the ACES filmic curve
+    Aces,
+
+    /// AgX-style log encoding with a sigmoid contrast curve
+    Agx,
+
+    /// Debug view of luminance, a stop per colour band
+    FalseColor,
+}
+
+impl Tonemapper {
+    pub const ALL: [Self; 6] = [
+        Self::Exposure,
+        Self::Reinhard,
+        Self::ExtendedReinhard,
+        Self::Aces,
+        Self::Agx,
+        Self::FalseColor,
+    ];
+
+    /// The next operator, wrapping around
+    pub fn next(self) -> Self {
+        let idx = Self::ALL.iter().position(|&op| op == self).unwrap_or(0);
+
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+
+    /// The curve from `hdr.wgsl`, for tonemapping on the CPU
+    fn apply(self, hdr: Vec3, white_point: f32) -> Vec3 {
+        let each = |v: Vec3, f: fn(f32) -> f32| Vec3::new(f(v.x), f(v.y), f(v.z));
+
+        match self {
+            Self::Exposure => Vec3::ONE - each(-hdr, f32::exp),
+            Self::Reinhard => hdr / (hdr + Vec3::ONE),
+
+            Self::ExtendedReinhard => {
+                let lum = luminance(hdr);
+
+                if lum <= 0.0 {
+                    return Vec3::ZERO;
+                }
+
+                let mapped = lum * (1.0 + lum / (white_point * white_point)) / (1.0 + lum);
+
+                hdr * (mapped / lum)
+            }
+
+            Self::Aces => {
+                let x = hdr * 0.6;
+
+                ((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14))
+                    .clamp(Vec3::ZERO, Vec3::ONE)
+            }
+
+            Self::Agx => {
+                let inset = Mat3::from_cols(
+                    Vec3::new(0.842_479_06, 0.042_328_242, 0.042_375_655),
+                    Vec3::new(0.078_433_6, 0.878_468_6, 0.078_433_6),
+                    Vec3::new(0.079_223_745, 0.079_166_13, 0.879_143),
+                );
+
+                let outset = Mat3::from_cols(
+                    Vec3::new(1.196_879, -0.052_896_852, -0.052_971_635),
+                    Vec3::new(-0.098_020_88, 1.151_903_1, -0.098_043_45),
+                    Vec3::new(-0.099_029_74, -0.098_961_18, 1.151_073_7),
+                );
+
+                let (min_ev, max_ev) = (-12.473_93, 4.026_069);
+
+                let x = each(inset * hdr.max(Vec3::splat(1e-10)), f32::log2);
+                let x = (x.clamp(Vec3::splat(min_ev), Vec3::splat(max_ev)) - min_ev)
+                    / (max_ev - min_ev);
+
+                let x2 = x * x;
+                let x4 = x2 * x2;
+                let contrast = 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x
+                    + 0.4298 * x2
+                    + 0.1191 * x
+                    - 0.00232;
+
+                // the curve's output is display-encoded, bring it back to linear
+                each((outset * contrast).max(Vec3::ZERO), |c| c.powf(2.2))
+            }
+
+            Self::FalseColor => {
+                let lum = luminance(hdr);
+
+                if lum <= 0.0 {
+                    return Vec3::ZERO;
+                }
+
+                let bands = [
+                    Vec3::ZERO,
+                    Vec3::Z,
+                    Vec3::new(0.0, 1.0, 1.0),
+                    Vec3::Y,
+                    Vec3::new(1.0, 1.0, 0.0),
+                    Vec3::X,
+                    Vec3::ONE,
+                ];
+
+                let t = ((lum.log2() + 6.0) / 2.0).clamp(0.0, 6.0);
+                let band = (t as usize).min(5);
+
+                bands[band].lerp(bands[band + 1], t - band as f32)
+            }
+        }
+    }
+}
+
+/// Relative luminance of a linear colour, weighted as the shaders do
+pub fn luminance(color: Vec3) -> f32 {
+    color.dot(Vec3::new(0.2126, 0.7152, 0.0722))
+}
+
+/// Tunable post-processing parameters, uploaded to the shaders as uniforms
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PostSettings {
+    /// Scene brightness multiplier applied before tonemapping
+    pub exposure: f32,
+
+    /// Gamma applied after tonemapping; 1.0 leaves the output as is
+    pub gamma: f32,
+
+    /// Luminance a pixel needs before it blooms
+    pub bloom_threshold: f32,
+
+    /// Trails are divided by this every frame
+    pub trail_decay: f32,
+
+    /// Most opacity a trail carries from one frame to the next
+    pub trail_persistence: f32,
+
+    pub bloom_mode: BloomMode,
+
+    /// Blur passes spreading a Gaussian bloom, rounded up to pairs of horizontal and vertical
+    pub blur_iterations: u32,
+
+    /// Half-size levels a dual-filter bloom goes down; each one doubles how far the glow reaches
+    pub bloom_radius: u32,
+
+    /// Strength of the glow added back on to the frame
+    pub bloom_intensity: f32,
+
+    pub tonemapper: Tonemapper,
+
+    /// Luminance mapped to pure white by the extended Reinhard operator
+    pub white_point: f32,
+
+    /// Adapt exposure to the average brightness of the frame, with `exposure` as compensation
+    pub auto_exposure: bool,
+
+    /// How quickly exposure adapts, in e-foldings per second
+    pub adaptation_speed: f32,
+
+    /// Darkest average luminance, in EV, that exposure will adapt to
+    pub min_ev: f32,
+
+    /// Brightest average luminance, in EV, that exposure will adapt to
+    pub max_ev: f32,
+}
+
+impl PostSettings {
+    /// Layout shared by the `PostSettings` struct in the shaders, padded to 16 bytes
+    fn as_uniform(&self) -> [u32; 12] {
+        [
+            self.exposure.to_bits(),
+            self.gamma.to_bits(),
+            self.bloom_threshold.to_bits(),
+            self.trail_decay.to_bits(),
+            self.trail_persistence.to_bits(),
+            self.white_point.to_bits(),
+            self.tonemapper as u32,
+            self.auto_exposure as u32,
+            self.min_ev.to_bits(),
+            self.max_ev.to_bits(),
+            self.bloom_intensity.to_bits(),
+            0,
+        ]
+    }
+
+    /// Tonemap a linear colour the way `HDRPass` does, `adapted` being the average log2 luminance
+    /// that auto exposure has settled on
+    pub fn tonemap(&self, hdr: Vec3, adapted: f32) -> Vec3 {
+        let exposure = if self.auto_exposure {
+            self.exposure / adapted.exp2()
+        } else {
+            self.exposure
+        };
+
+        let mapped = self.tonemapper.apply(hdr * exposure, self.white_point);
+
+        Vec3::new(
+            mapped.x.powf(self.gamma.recip()),
+            mapped.y.powf(self.gamma.recip()),
+            mapped.z.powf(self.gamma.recip()),
+        )
+    }
+}
+
+impl Default for PostSettings {
+    fn default() -> Self {
+        Self {
+            exposure: 1.0,
+            gamma: 1.0,
+            bloom_threshold: 1.0,
+            trail_decay: 1.25,
+            trail_persistence: 0.95,
+            bloom_mode: BloomMode::default(),
+            blur_iterations: 10,
+            bloom_radius: 6,
+            bloom_intensity: 1.0,
+            tonemapper: Tonemapper::default(),
+            white_point: 4.0,
+            auto_exposure: false,
+            adaptation_speed: 1.5,
+            min_ev: -8.0,
+            max_ev: 8.0,
+        }
+    }
+}
+
+/// Fraction of the way from the adapted exposure to the measured one to move over `frame_time` seconds
+fn adaptation_rate(settings: &PostSettings, frame_time: f32) -> f32 {
+    1.0 - (-settings.adaptation_speed * frame_time).exp()
+}
+
+/// `PostSettings` along with the buffer and bind group that carry them to the shaders
+pub struct PostUniforms {
+    settings: PostSettings,
+    buffer: Buffer,
+    layout: BindGroupLayout,
+    group: BindGroup,
+}
+
+impl PostUniforms {
+    fn new(device: &Device, settings: PostSettings) -> Self {
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("post_settings_buffer"),
+            contents: bytemuck::cast_slice(&settings.as_uniform()),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: pixels::wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(std::mem::size_of::<[u32; 12]>() as _),
+                },
+                count: None,
+            }],
+        });
+
+        let group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("post_settings_bind_group"),
+            layout: &layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            settings,
+            buffer,
+            layout,
+            group,
+        }
+    }
+
+    fn write(&mut self, queue: &Queue, settings: PostSettings) {
+        queue.write_buffer(
+            &self.buffer,
+            0,
+            bytemuck::cast_slice(&settings.as_uniform()),
+        );
+        self.settings = settings;
+    }
+
+    pub fn settings(&self) -> &PostSettings {
+        &self.settings
+    }
+
+    pub fn layout(&self) -> &BindGroupLayout {
+        &self.layout
+    }
+
+    pub fn group(&self) -> &BindGroup {
+        &self.group
+    }
+}
+
+struct PostEntry {
+    pass: Box<dyn PostPass>,
+    enabled: bool,
+}
+
+/// Runs the post-processing chain over the scaled canvas, then tonemaps it on to the surface
 pub struct ShaderPipeline {
     texture: TextureView,
-    trail: TrailPass,
-    bloom: BloomPass,
+    vertex_module: ShaderModule,
+    passes: Vec<PostEntry>,
+    uniforms: PostUniforms,
+    exposure: ExposurePass,
     hdr: HDRPass,
+    width: u32,
+    height: u32,
+
+    /// Real time (s) since the last frame, for exposure adaptation
+    frame_time: f32,
 }
 
 impl ShaderPipeline {
     pub fn new(pixels: &pixels::Pixels, width: u32, height: u32) -> Self {
+        Self::with_config(pixels, width, height, &RenderConfig::default())
+    }
+
+    pub fn with_config(
+        pixels: &pixels::Pixels,
+        width: u32,
+        height: u32,
+        config: &RenderConfig,
+    ) -> Self {
         let device = pixels.device();
 
         let output_texture_format = pixels.surface_texture_format();
 
-        let texture = Self::create_resources(device, height, width);
+        let texture = Self::create_resources(device, width, height);
 
-        let shader_module = device.create_shader_module(&include_wgsl!("shaders/vert.wgsl"));
+        let vertex_module = device.create_shader_module(&include_wgsl!("shaders/vert.wgsl"));
 
-        let vertex = VertexState {
-            module: &shader_module,
-            entry_point: "main",
-            buffers: &[],
-        };
+        let uniforms = PostUniforms::new(device, config.settings);
 
-        let trail = TrailPass::new(device, &texture, width, height, vertex.clone());
-
-        let bloom = BloomPass::new(device, &texture, width, height, vertex.clone());
+        let exposure = ExposurePass::new(
+            device,
+            &texture,
+            width,
+            height,
+            Self::vertex(&vertex_module),
+            &uniforms,
+        );
 
-        let hdr = HDRPass::new(device, &texture, output_texture_format, vertex);
+        let hdr = HDRPass::new(
+            device,
+            &texture,
+            output_texture_format,
+            Self::vertex(&vertex_module),
+            &uniforms,
+            exposure.adapted(),
+        );
 
-        Self {
+        let mut pipeline = Self {
             texture,
-            trail,
+            vertex_module,
+            passes: Vec::new(),
+            uniforms,
+            exposure,
             hdr,
-            bloom,
+            width,
+            height,
+            frame_time: 0.0,
+        };
+
+        pipeline.set_passes(pixels, &config.passes);
+
+        pipeline
+    }
+
+    fn vertex(module: &ShaderModule) -> VertexState<'_> {
+        VertexState {
+            module,
+            entry_point: "main",
+            buffers: &[],
         }
     }
 
@@ -99,10 +581,125 @@ impl ShaderPipeline {
     pub fn resize(&mut self, pixels: &pixels::Pixels, width: u32, height: u32) {
         let device = pixels.device();
         self.texture = Self::create_resources(device, width, height);
+        self.width = width;
+        self.height = height;
+
+        for entry in &mut self.passes {
+            entry.pass.resize(device, &self.texture, width, height);
+        }
+
+        self.exposure.resize(device, &self.texture, width, height);
+        self.hdr
+            .resize(device, &self.texture, self.exposure.adapted());
+    }
+
+    /// The chain as it currently stands, in order
+    pub fn passes(&self) -> Vec<PassConfig> {
+        self.passes
+            .iter()
+            .map(|entry| PassConfig {
+                kind: entry.pass.kind(),
+                enabled: entry.enabled,
+            })
+            .collect()
+    }
+
+    /// The chain and settings as they currently stand, for saving with a scene
+    pub fn config(&self) -> RenderConfig {
+        RenderConfig {
+            passes: self.passes(),
+            settings: *self.settings(),
+        }
+    }
+
+    /// Replace the chain, reusing existing passes of the same kind where there are any
+    pub fn set_passes(&mut self, pixels: &pixels::Pixels, passes: &[PassConfig]) {
+        let device = pixels.device();
+        let mut old = std::mem::take(&mut self.passes);
+
+        for config in passes {
+            let pass = match old
+                .iter()
+                .position(|entry| entry.pass.kind() == config.kind)
+            {
+                Some(idx) => old.remove(idx).pass,
+                None => config.kind.create(
+                    device,
+                    &self.texture,
+                    self.width,
+                    self.height,
+                    Self::vertex(&self.vertex_module),
+                    &self.uniforms,
+                ),
+            };
+
+            self.passes.push(PostEntry {
+                pass,
+                enabled: config.enabled,
+            });
+        }
+    }
+
+    /// Enable or disable every pass of a kind, returning whether the chain has one
+    pub fn set_enabled(&mut self, kind: PassKind, enabled: bool) -> bool {
+        let mut found = false;
+
+        for entry in self
+            .passes
+            .iter_mut()
+            .filter(|entry| entry.pass.kind() == kind)
+        {
+            entry.enabled = enabled;
+            found = true;
+        }
+
+        found
+    }
+
+    /// Flip every pass of a kind on or off
+    pub fn toggle(&mut self, kind: PassKind) {
+        for entry in self
+            .passes
+            .iter_mut()
+            .filter(|entry| entry.pass.kind() == kind)
+        {
+            entry.enabled = !entry.enabled;
+        }
+    }
+
+    /// Move the pass at `from` so it runs at position `to`
+    pub fn move_pass(&mut self, from: usize, to: usize) {
+        if from < self.passes.len() && to < self.passes.len() {
+            let entry = self.passes.remove(from);
+            self.passes.insert(to, entry);
+        }
+    }
+
+    pub fn settings(&self) -> &PostSettings {
+        self.uniforms.settings()
+    }
+
+    pub fn tonemapper(&self) -> Tonemapper {
+        self.settings().tonemapper
+    }
+
+    pub fn set_tonemapper(&mut self, pixels: &pixels::Pixels, tonemapper: Tonemapper) {
+        let settings = PostSettings {
+            tonemapper,
+            ..*self.settings()
+        };
+
+        self.set_settings(pixels, settings);
+    }
+
+    /// Let the pipeline know how much real time the coming frame covers
+    pub fn advance(&mut self, elapsed: Duration) {
+        self.frame_time = elapsed.as_secs_f32();
+    }
 
-        self.trail.resize(device, &self.texture, width, height);
-        self.bloom.resize(device, &self.texture, width, height);
-        self.hdr.resize(device, &self.texture);
+    /// Upload new settings, taking effect from the next frame rendered
+    pub fn set_settings(&mut self, pixels: &pixels::Pixels, settings: PostSettings) {
+        self.uniforms.write(pixels.queue(), settings);
     }
 
     pub fn render(
@@ -111,9 +708,19 @@ impl ShaderPipeline {
         target: &TextureView,
         clip: (u32, u32, u32, u32),
     ) {
-        self.bloom.render(encoder, &self.texture, clip);
-        self.trail.render(encoder, &self.texture, clip);
-        self.hdr.render(encoder, target, clip);
+        for entry in self.passes.iter().filter(|entry| entry.enabled) {
+            entry
+                .pass
+                .render(encoder, &self.texture, clip, &self.uniforms);
+        }
+
+        if self.settings().auto_exposure {
+            let rate = adaptation_rate(self.settings(), self.frame_time);
+
+            self.exposure.render(encoder, rate, &self.uniforms);
+        }
+
+        self.hdr.render(encoder, target, clip, &self.uniforms);
     }
 }
 
@@ -136,6 +743,7 @@ impl TrailPass {
         width: u32,
         height: u32,
         vertex: VertexState<'_>,
+        uniforms: &PostUniforms,
     ) -> Self {
         let sampler = create_sampler(device);
 
@@ -230,7 +838,7 @@ impl TrailPass {
             label: Some("trail_cut_pipeline"),
             layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
                 label: None,
-                bind_group_layouts: &[&cut_group_layout],
+                bind_group_layouts: &[&cut_group_layout, uniforms.layout()],
                 push_constant_ranges: &[],
             })),
             vertex,
@@ -317,8 +925,14 @@ impl TrailPass {
 
         (cut_buffer, send_buffer, mix_group, cut_group)
     }
+}
 
-    pub fn resize(&mut self, device: &Device, input: &TextureView, width: u32, height: u32) {
+impl PostPass for TrailPass {
+    fn kind(&self) -> PassKind {
+        PassKind::Trail
+    }
+
+    fn resize(&mut self, device: &Device, input: &TextureView, width: u32, height: u32) {
         let (cut_buffer, send_buffer, mix_group, cut_group) = Self::create_resources(
             device,
             input,
@@ -335,11 +949,12 @@ impl TrailPass {
         self.cut_group = cut_group;
     }
 
-    pub fn render(
+    fn render(
         &self,
         encoder: &mut CommandEncoder,
         target: &TextureView,
         clip: (u32, u32, u32, u32),
+        uniforms: &PostUniforms,
     ) {
         {
             let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
@@ -387,6 +1002,7 @@ impl TrailPass {
 
             pass.set_pipeline(&self.cut_pipeline);
             pass.set_bind_group(0, &self.cut_group, &[]);
+            pass.set_bind_group(1, uniforms.group(), &[]);
             pass.set_scissor_rect(clip.0, clip.1, clip.2, clip.3);
             pass.draw(0..3, 0..1);
         }
@@ -410,6 +1026,66 @@ pub struct BloomPass {
     even_buffer: TextureView,
     odd_buffer: TextureView,
     sampler: Sampler,
+    down_pipeline: RenderPipeline,
+    up_pipeline: RenderPipeline,
+    linear_sampler: Sampler,
+    chain: BloomChain,
+}
+
+/// Most half-size levels a dual-filter bloom can go down
+const BLOOM_MAX_LEVELS: usize = 8;
+
+/// Successively halved textures for dual-filter bloom, fed from the bright pixels
+struct BloomChain {
+    source: BindGroup,
+    levels: Vec<(TextureView, BindGroup)>,
+}
+
+impl BloomChain {
+    fn new(
+        device: &Device,
+        source: &TextureView,
+        width: u32,
+        height: u32,
+        layout: &BindGroupLayout,
+        sampler: &Sampler,
+    ) -> Self {
+        let group = |view: &TextureView| {
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some("bloom_chain_bind_group"),
+                layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::Sampler(sampler),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::TextureView(view),
+                    },
+                ],
+            })
+        };
+
+        let (mut level_width, mut level_height) = (width.max(1), height.max(1));
+        let mut levels = Vec::new();
+
+        while levels.len() < BLOOM_MAX_LEVELS && (level_width > 1 || level_height > 1) {
+            level_width = level_width.div_ceil(2);
+            level_height = level_height.div_ceil(2);
+
+            let view =
+                create_texture_view(device, level_width, level_height, PIPELINE_TEXTURE_FORMAT);
+            let level_group = group(&view);
+
+            levels.push((view, level_group));
+        }
+
+        Self {
+            source: group(source),
+            levels,
+        }
+    }
 }
 
 impl BloomPass {
@@ -419,6 +1095,7 @@ impl BloomPass {
         width: u32,
         height: u32,
         vertex: VertexState<'_>,
+        uniforms: &PostUniforms,
     ) -> Self {
         let sampler = create_sampler(device);
 
@@ -565,7 +1242,7 @@ impl BloomPass {
             label: Some("bloom_split_pipeline"),
             layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
                 label: None,
-                bind_group_layouts: &[&split_group_layout],
+                bind_group_layouts: &[&split_group_layout, uniforms.layout()],
                 push_constant_ranges: &[],
             })),
             vertex: vertex.clone(),
@@ -618,10 +1295,10 @@ impl BloomPass {
             label: Some("bloom_combine_pipeline"),
             layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
                 label: None,
-                bind_group_layouts: &[&combine_group_layout],
+                bind_group_layouts: &[&combine_group_layout, uniforms.layout()],
                 push_constant_ranges: &[],
             })),
-            vertex,
+            vertex: vertex.clone(),
             fragment: Some(FragmentState {
                 module: &device.create_shader_module(&include_wgsl!("shaders/combine.wgsl")),
                 entry_point: "main",
@@ -637,6 +1314,71 @@ impl BloomPass {
             multiview: None,
         });
 
+        let dual_pipeline = |label, shader, blend| {
+            device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                    label: None,
+                    bind_group_layouts: &[&blur_group_layout],
+                    push_constant_ranges: &[],
+                })),
+                vertex: vertex.clone(),
+                fragment: Some(FragmentState {
+                    module: &device.create_shader_module(&shader),
+                    entry_point: "main",
+                    targets: &[ColorTargetState {
+                        format: PIPELINE_TEXTURE_FORMAT,
+                        blend: Some(blend),
+                        write_mask: ColorWrites::ALL,
+                    }],
+                }),
+                primitive: PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: MultisampleState::default(),
+                multiview: None,
+            })
+        };
+
+        let down_pipeline = dual_pipeline(
+            "bloom_down_pipeline",
+            include_wgsl!("shaders/dual_down.wgsl"),
+            BlendState::REPLACE,
+        );
+
+        // each level is added on to the one above it on the way back up
+        let up_pipeline = dual_pipeline(
+            "bloom_up_pipeline",
+            include_wgsl!("shaders/dual_up.wgsl"),
+            BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::One,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent::REPLACE,
+            },
+        );
+
+        // the dual filter relies on bilinear taps landing between texels
+        let linear_sampler = device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let chain = BloomChain::new(
+            device,
+            &even_buffer,
+            width,
+            height,
+            &blur_group_layout,
+            &linear_sampler,
+        );
+
         Self {
             split_pipeline,
             split_group_layout,
@@ -654,6 +1396,10 @@ impl BloomPass {
             even_buffer,
             odd_buffer,
             sampler,
+            down_pipeline,
+            up_pipeline,
+            linear_sampler,
+            chain,
         }
     }
 
@@ -756,7 +1502,119 @@ impl BloomPass {
         )
     }
 
-    pub fn resize(&mut self, device: &Device, input: &TextureView, width: u32, height: u32) {
+    /// Blur the bright pixels at full resolution, leaving the result in the even buffer
+    fn blur(&self, encoder: &mut CommandEncoder, clip: (u32, u32, u32, u32), iterations: u32) {
+        // the combine step reads the even buffer, so finish on a vertical pass
+        let iterations = (iterations + 1) & !1;
+
+        for i in 0..iterations {
+            let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("bloom_blur_pass"),
+                color_attachments: &[RenderPassColorAttachment {
+                    view: if i % 2 == 1 {
+                        &self.even_buffer
+                    } else {
+                        &self.odd_buffer
+                    },
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::BLACK),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+
+            pass.set_pipeline(&self.blur_pipeline);
+            pass.set_bind_group(
+                0,
+                if i % 2 == 1 {
+                    &self.blur_odd_group
+                } else {
+                    &self.blur_even_group
+                },
+                &[],
+            );
+            pass.set_bind_group(
+                1,
+                if i % 2 == 0 {
+                    &self.blur_pass_h_group
+                } else {
+                    &self.blur_pass_v_group
+                },
+                &[],
+            );
+            pass.set_scissor_rect(clip.0, clip.1, clip.2, clip.3);
+            pass.draw(0..3, 0..1);
+        }
+    }
+
+    /// Take the bright pixels down the chain and back up, leaving the glow in the even buffer
+    fn dual_filter(&self, encoder: &mut CommandEncoder, radius: u32) {
+        let depth = (radius as usize).clamp(1, BLOOM_MAX_LEVELS);
+        let levels = &self.chain.levels[..depth.min(self.chain.levels.len())];
+
+        let draw = |encoder: &mut CommandEncoder,
+                    label,
+                    target: &TextureView,
+                    load,
+                    pipeline: &RenderPipeline,
+                    group: &BindGroup| {
+            let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some(label),
+                color_attachments: &[RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: Operations { load, store: true },
+                }],
+                depth_stencil_attachment: None,
+            });
+
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, group, &[]);
+            pass.draw(0..3, 0..1);
+        };
+
+        for (idx, (target, _)) in levels.iter().enumerate() {
+            let source = match idx {
+                0 => &self.chain.source,
+                _ => &levels[idx - 1].1,
+            };
+
+            draw(
+                encoder,
+                "bloom_down_pass",
+                target,
+                LoadOp::Clear(Color::BLACK),
+                &self.down_pipeline,
+                source,
+            );
+        }
+
+        for (idx, (_, source)) in levels.iter().enumerate().rev() {
+            let target = match idx {
+                0 => &self.even_buffer,
+                _ => &levels[idx - 1].0,
+            };
+
+            draw(
+                encoder,
+                "bloom_up_pass",
+                target,
+                LoadOp::Load,
+                &self.up_pipeline,
+                source,
+            );
+        }
+    }
+}
+
+impl PostPass for BloomPass {
+    fn kind(&self) -> PassKind {
+        PassKind::Bloom
+    }
+
+    fn resize(&mut self, device: &Device, input: &TextureView, width: u32, height: u32) {
         let (
             even_buffer,
             odd_buffer,
@@ -783,13 +1641,23 @@ impl BloomPass {
         self.blur_even_group = blur_even_group;
         self.blur_odd_group = blur_odd_group;
         self.combine_group = combine_group;
+
+        self.chain = BloomChain::new(
+            device,
+            &self.even_buffer,
+            width,
+            height,
+            &self.blur_group_layout,
+            &self.linear_sampler,
+        );
     }
 
-    pub fn render(
+    fn render(
         &self,
         encoder: &mut CommandEncoder,
         target: &TextureView,
         clip: (u32, u32, u32, u32),
+        uniforms: &PostUniforms,
     ) {
         {
             let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
@@ -817,19 +1685,21 @@ impl BloomPass {
 
             pass.set_pipeline(&self.split_pipeline);
             pass.set_bind_group(0, &self.split_group, &[]);
+            pass.set_bind_group(1, uniforms.group(), &[]);
             pass.set_scissor_rect(clip.0, clip.1, clip.2, clip.3);
             pass.draw(0..3, 0..1);
         }
 
-        for i in 0..10 {
+        match uniforms.settings().bloom_mode {
+            BloomMode::Gaussian => self.blur(encoder, clip, uniforms.settings().blur_iterations),
+            BloomMode::DualFilter => self.dual_filter(encoder, uniforms.settings().bloom_radius),
+        }
+
+        {
             let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
-                label: Some("bloom_blur_pass"),
+                label: Some("bloom_combine_pass"),
                 color_attachments: &[RenderPassColorAttachment {
-                    view: if i % 2 == 1 {
-                        &self.even_buffer
-                    } else {
-                        &self.odd_buffer
-                    },
+                    view: target,
                     resolve_target: None,
                     ops: Operations {
                         load: LoadOp::Clear(Color::BLACK),
@@ -839,32 +1709,193 @@ impl BloomPass {
                 depth_stencil_attachment: None,
             });
 
-            pass.set_pipeline(&self.blur_pipeline);
-            pass.set_bind_group(
-                0,
-                if i % 2 == 1 {
-                    &self.blur_odd_group
-                } else {
-                    &self.blur_even_group
-                },
-                &[],
-            );
-            pass.set_bind_group(
-                1,
-                if i % 2 == 0 {
-                    &self.blur_pass_h_group
-                } else {
-                    &self.blur_pass_v_group
-                },
-                &[],
-            );
+            pass.set_pipeline(&self.combine_pipeline);
+            pass.set_bind_group(0, &self.combine_group, &[]);
+            pass.set_bind_group(1, uniforms.group(), &[]);
             pass.set_scissor_rect(clip.0, clip.1, clip.2, clip.3);
             pass.draw(0..3, 0..1);
         }
+    }
+}
+
+/// Measures the average luminance of the frame and eases the adapted exposure towards it.
+///
+/// Log luminance is averaged down a chain of half-size levels to a single texel, then blended
+/// into a 1x1 texture that `HDRPass` reads, with the blend constant setting the adaptation rate
+pub struct ExposurePass {
+    luminance_pipeline: RenderPipeline,
+    downsample_pipeline: RenderPipeline,
+    adapt_pipeline: RenderPipeline,
+    level_layout: BindGroupLayout,
+    input_group: BindGroup,
+    levels: Vec<(TextureView, BindGroup)>,
+    adapted: TextureView,
+}
+
+impl ExposurePass {
+    const LEVEL_FORMAT: TextureFormat = TextureFormat::Rg16Float;
+    const ADAPTED_FORMAT: TextureFormat = TextureFormat::R16Float;
+
+    pub fn new(
+        device: &Device,
+        input: &TextureView,
+        width: u32,
+        height: u32,
+        vertex: VertexState<'_>,
+        uniforms: &PostUniforms,
+    ) -> Self {
+        // everything is read with textureLoad, so no sampler and no filtering
+        let level_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: false },
+                    multisampled: false,
+                    view_dimension: TextureViewDimension::D2,
+                },
+                count: None,
+            }],
+        });
+
+        let pipeline = |label, shader, layouts: &[&BindGroupLayout], format, blend| {
+            device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                    label: None,
+                    bind_group_layouts: layouts,
+                    push_constant_ranges: &[],
+                })),
+                vertex: vertex.clone(),
+                fragment: Some(FragmentState {
+                    module: &device.create_shader_module(&shader),
+                    entry_point: "main",
+                    targets: &[ColorTargetState {
+                        format,
+                        blend: Some(blend),
+                        write_mask: ColorWrites::ALL,
+                    }],
+                }),
+                primitive: PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: MultisampleState::default(),
+                multiview: None,
+            })
+        };
+
+        let luminance_pipeline = pipeline(
+            "exposure_luminance_pipeline",
+            include_wgsl!("shaders/luminance.wgsl"),
+            &[&level_layout],
+            Self::LEVEL_FORMAT,
+            BlendState::REPLACE,
+        );
+
+        let downsample_pipeline = pipeline(
+            "exposure_downsample_pipeline",
+            include_wgsl!("shaders/downsample.wgsl"),
+            &[&level_layout],
+            Self::LEVEL_FORMAT,
+            BlendState::REPLACE,
+        );
+
+        // adapted = target * rate + adapted * (1 - rate)
+        let adapt_component = BlendComponent {
+            src_factor: BlendFactor::Constant,
+            dst_factor: BlendFactor::OneMinusConstant,
+            operation: BlendOperation::Add,
+        };
+
+        let adapt_pipeline = pipeline(
+            "exposure_adapt_pipeline",
+            include_wgsl!("shaders/adapt.wgsl"),
+            &[&level_layout, uniforms.layout()],
+            Self::ADAPTED_FORMAT,
+            BlendState {
+                color: adapt_component,
+                alpha: adapt_component,
+            },
+        );
+
+        let (input_group, levels) =
+            Self::create_resources(device, input, width, height, &level_layout);
+
+        let adapted = create_texture_view(device, 1, 1, Self::ADAPTED_FORMAT);
+
+        Self {
+            luminance_pipeline,
+            downsample_pipeline,
+            adapt_pipeline,
+            level_layout,
+            input_group,
+            levels,
+            adapted,
+        }
+    }
 
-        {
+    fn create_resources(
+        device: &Device,
+        input: &TextureView,
+        width: u32,
+        height: u32,
+        layout: &BindGroupLayout,
+    ) -> (BindGroup, Vec<(TextureView, BindGroup)>) {
+        let group = |view: &TextureView| {
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some("exposure_level_bind_group"),
+                layout,
+                entries: &[BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(view),
+                }],
+            })
+        };
+
+        let input_group = group(input);
+
+        // full size, then halved (rounding up) until there's a single texel left
+        let mut levels = Vec::new();
+        let (mut level_width, mut level_height) = (width.max(1), height.max(1));
+
+        loop {
+            let view = create_texture_view(device, level_width, level_height, Self::LEVEL_FORMAT);
+            let level_group = group(&view);
+            levels.push((view, level_group));
+
+            if level_width == 1 && level_height == 1 {
+                break;
+            }
+
+            level_width = level_width.div_ceil(2);
+            level_height = level_height.div_ceil(2);
+        }
+
+        (input_group, levels)
+    }
+
+    pub fn resize(&mut self, device: &Device, input: &TextureView, width: u32, height: u32) {
+        let (input_group, levels) =
+            Self::create_resources(device, input, width, height, &self.level_layout);
+
+        self.input_group = input_group;
+        self.levels = levels;
+    }
+
+    /// Adapted log2 luminance, a single R16Float texel
+    pub fn adapted(&self) -> &TextureView {
+        &self.adapted
+    }
+
+    /// Measure the frame and adapt, `rate` being the fraction of the way to move this frame
+    pub fn render(&self, encoder: &mut CommandEncoder, rate: f32, uniforms: &PostUniforms) {
+        let stage = |encoder: &mut CommandEncoder,
+                     label,
+                     target: &TextureView,
+                     pipeline: &RenderPipeline,
+                     group: &BindGroup| {
             let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
-                label: Some("bloom_combine_pass"),
+                label: Some(label),
                 color_attachments: &[RenderPassColorAttachment {
                     view: target,
                     resolve_target: None,
@@ -876,11 +1907,59 @@ impl BloomPass {
                 depth_stencil_attachment: None,
             });
 
-            pass.set_pipeline(&self.combine_pipeline);
-            pass.set_bind_group(0, &self.combine_group, &[]);
-            pass.set_scissor_rect(clip.0, clip.1, clip.2, clip.3);
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, group, &[]);
             pass.draw(0..3, 0..1);
+        };
+
+        stage(
+            encoder,
+            "exposure_luminance_pass",
+            &self.levels[0].0,
+            &self.luminance_pipeline,
+            &self.input_group,
+        );
+
+        for pair in self.levels.windows(2) {
+            stage(
+                encoder,
+                "exposure_downsample_pass",
+                &pair[1].0,
+                &self.downsample_pipeline,
+                &pair[0].1,
+            );
         }
+
+        let (_, average) = self
+            .levels
+            .last()
+            .expect("exposure chain has at least one level");
+
+        let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("exposure_adapt_pass"),
+            color_attachments: &[RenderPassColorAttachment {
+                view: &self.adapted,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+
+        let rate = rate.clamp(0.0, 1.0) as f64;
+
+        pass.set_pipeline(&self.adapt_pipeline);
+        pass.set_bind_group(0, average, &[]);
+        pass.set_bind_group(1, uniforms.group(), &[]);
+        pass.set_blend_constant(Color {
+            r: rate,
+            g: rate,
+            b: rate,
+            a: rate,
+        });
+        pass.draw(0..3, 0..1);
     }
 }
 
@@ -897,6 +1976,8 @@ impl HDRPass {
         texture: &TextureView,
         surface_format: TextureFormat,
         vertex: VertexState<'_>,
+        uniforms: &PostUniforms,
+        adapted: &TextureView,
     ) -> Self {
         let shader = device.create_shader_module(&include_wgsl!("shaders/hdr.wgsl"));
 
@@ -921,16 +2002,26 @@ impl HDRPass {
                     },
                     count: None,
                 },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        multisampled: false,
+                        view_dimension: TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
             ],
         });
 
-        let group = Self::create_resources(device, &group_layout, texture, &sampler);
+        let group = Self::create_resources(device, &group_layout, texture, adapted, &sampler);
 
         let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
             label: Some("hdr_render_pipeline"),
             layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
                 label: None,
-                bind_group_layouts: &[&group_layout],
+                bind_group_layouts: &[&group_layout, uniforms.layout()],
                 push_constant_ranges: &[],
             })),
             vertex,
@@ -961,6 +2052,7 @@ impl HDRPass {
         device: &Device,
         layout: &BindGroupLayout,
         input: &TextureView,
+        adapted: &TextureView,
         sampler: &Sampler,
     ) -> BindGroup {
         device.create_bind_group(&BindGroupDescriptor {
@@ -975,12 +2067,17 @@ impl HDRPass {
                     binding: 1,
                     resource: BindingResource::TextureView(input),
                 },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::TextureView(adapted),
+                },
             ],
         })
     }
 
-    pub fn resize(&mut self, device: &Device, texture: &TextureView) {
-        self.group = Self::create_resources(device, &self.group_layout, texture, &self.sampler);
+    pub fn resize(&mut self, device: &Device, texture: &TextureView, adapted: &TextureView) {
+        self.group =
+            Self::create_resources(device, &self.group_layout, texture, adapted, &self.sampler);
     }
 
     pub fn render(
@@ -988,6 +2085,7 @@ impl HDRPass {
         encoder: &mut CommandEncoder,
         target: &TextureView,
         clip: (u32, u32, u32, u32),
+        uniforms: &PostUniforms,
     ) {
         let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
             label: Some("hdr_render_pass"),
@@ -1004,7 +2102,85 @@ impl HDRPass {
 
         pass.set_pipeline(&self.pipeline);
         pass.set_bind_group(0, &self.group, &[]);
+        pass.set_bind_group(1, uniforms.group(), &[]);
         pass.set_scissor_rect(clip.0, clip.1, clip.2, clip.3);
         pass.draw(0..3, 0..1);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adaptation_follows_frame_time() {
+        let settings = PostSettings::default();
+
+        assert_eq!(adaptation_rate(&settings, 0.0), 0.0);
+
+        // one e-folding
+        let rate = adaptation_rate(&settings, 1.0 / settings.adaptation_speed);
+        assert!((rate - (1.0 - (-1f32).exp())).abs() < 1e-6);
+
+        // two short frames cover the same ground as one long one
+        let short = adaptation_rate(&settings, 1.0 / 120.0);
+        let long = adaptation_rate(&settings, 1.0 / 60.0);
+        assert!((1.0 - (1.0 - short) * (1.0 - short) - long).abs() < 1e-6);
+
+        assert!(adaptation_rate(&settings, 60.0) > 0.999);
+    }
+
+    #[test]
+    fn cpu_tonemapping_is_well_behaved() {
+        let colors =
+            [0.0, 0.01, 0.5, 1.0, 4.0, 100.0].map(|value| Vec3::new(value, value * 0.5, 0.0));
+
+        for tonemapper in Tonemapper::ALL {
+            let settings = PostSettings {
+                tonemapper,
+                gamma: 2.2,
+                ..PostSettings::default()
+            };
+
+            for color in colors {
+                let mapped = settings.tonemap(color, 0.0);
+
+                // anything past 1 is clamped on the way out, just as the surface does
+                assert!(
+                    mapped.is_finite() && mapped.cmpge(Vec3::ZERO).all(),
+                    "{tonemapper:?} mapped {color} to {mapped}"
+                );
+            }
+        }
+
+        // black stays black, and brighter stays brighter, for all but the debug view
+        for tonemapper in &Tonemapper::ALL[..5] {
+            let settings = PostSettings {
+                tonemapper: *tonemapper,
+                ..PostSettings::default()
+            };
+
+            let [dark, dim, bright] =
+                [0.0, 0.2, 2.0].map(|value| luminance(settings.tonemap(Vec3::splat(value), 0.0)));
+
+            assert!(
+                dark < 1e-3 && dim < bright,
+                "{tonemapper:?}: {dark} {dim} {bright}"
+            );
+        }
+
+        // auto exposure brings the adapted luminance back to `exposure`
+        let settings = PostSettings {
+            auto_exposure: true,
+            tonemapper: Tonemapper::Reinhard,
+            ..PostSettings::default()
+        };
+
+        assert!(
+            (settings.tonemap(Vec3::splat(8.0), 3.0) - Vec3::splat(0.5))
+                .abs()
+                .max_element()
+                < 1e-6
+        );
+    }
+}
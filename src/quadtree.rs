@@ -0,0 +1,328 @@
+// MIT License
+
+// Copyright (c) 2022 AnonmousDapper
+
+use std::ops::Range;
+
+use crate::simulation::{Point, Velocity};
+
+/// Deeper than this, bodies share a leaf (coincident bodies would otherwise never separate)
+const MAX_DEPTH: u32 = 32;
+
+#[derive(Debug)]
+struct Node {
+    /// Centre of the node's square
+    center: Point,
+
+    /// Half the side length of the node's square
+    half: f32,
+
+    mass: f32,
+    center_of_mass: Point,
+
+    /// Index of the first of 4 consecutive children, 0 for leaves (the root is never a child)
+    children: usize,
+
+    /// Leaf bodies, as a range into `QuadTree::order`
+    bodies: Range<usize>,
+}
+
+impl Node {
+    fn contains(&self, point: Point) -> bool {
+        (point - self.center).abs().max_element() <= self.half
+    }
+}
+
+/// Barnes-Hut quadtree over a set of point masses
+#[derive(Debug)]
+pub struct QuadTree {
+    nodes: Vec<Node>,
+    order: Vec<usize>,
+    positions: Vec<Point>,
+    masses: Vec<f32>,
+}
+
+impl QuadTree {
+    pub fn new(positions: &[Point], masses: &[f32]) -> Self {
+        let (min, max) = positions.iter().fold(
+            (Point::splat(f32::INFINITY), Point::splat(f32::NEG_INFINITY)),
+            |(min, max), &pos| (min.min(pos), max.max(pos)),
+        );
+
+        let (center, half) = if positions.is_empty() {
+            (Point::ZERO, 1.0)
+        } else {
+            (
+                (min + max) * 0.5,
+                ((max - min).max_element() * 0.5).max(f32::EPSILON),
+            )
+        };
+
+        let mut tree = Self {
+            nodes: Vec::with_capacity(positions.len() * 2),
+            order: (0..positions.len()).collect(),
+            positions: positions.to_vec(),
+            masses: masses.to_vec(),
+        };
+
+        tree.nodes.push(Node {
+            center,
+            half,
+            mass: 0.0,
+            center_of_mass: center,
+            children: 0,
+            bodies: 0..positions.len(),
+        });
+
+        tree.subdivide(0, 0);
+
+        tree
+    }
+
+    fn subdivide(&mut self, node: usize, depth: u32) {
+        let (center, half, bodies) = {
+            let node = &self.nodes[node];
+            (node.center, node.half, node.bodies.clone())
+        };
+
+        // past float precision, splitting can't separate anything
+        let splittable = half > f32::EPSILON * center.abs().max_element().max(1.0);
+
+        if bodies.len() > 1 && splittable && depth < MAX_DEPTH {
+            // sort this node's bodies by quadrant, then hand each child its slice
+            let quadrant =
+                |pos: Point| (pos.x >= center.x) as usize | ((pos.y >= center.y) as usize) << 1;
+
+            let positions = &self.positions;
+            self.order[bodies.clone()].sort_unstable_by_key(|&idx| quadrant(positions[idx]));
+
+            let first_child = self.nodes.len();
+            let mut start = bodies.start;
+
+            for q in 0..4 {
+                let end = start
+                    + self.order[start..bodies.end]
+                        .iter()
+                        .take_while(|&&idx| quadrant(positions[idx]) == q)
+                        .count();
+
+                let offset = Point::new(
+                    if q & 1 == 1 { 0.5 } else { -0.5 },
+                    if q & 2 == 2 { 0.5 } else { -0.5 },
+                ) * half;
+
+                self.nodes.push(Node {
+                    center: center + offset,
+                    half: half * 0.5,
+                    mass: 0.0,
+                    center_of_mass: center + offset,
+                    children: 0,
+                    bodies: start..end,
+                });
+
+                start = end;
+            }
+
+            self.nodes[node].children = first_child;
+            self.nodes[node].bodies = 0..0;
+
+            for child in first_child..first_child + 4 {
+                self.subdivide(child, depth + 1);
+            }
+
+            let (mass, moment) = self.nodes[first_child..first_child + 4].iter().fold(
+                (0.0, Point::ZERO),
+                |(mass, moment), child| {
+                    (
+                        mass + child.mass,
+                        moment + child.center_of_mass * child.mass,
+                    )
+                },
+            );
+
+            self.set_mass(node, mass, moment);
+        } else {
+            let (mass, moment) =
+                self.order[bodies]
+                    .iter()
+                    .fold((0.0, Point::ZERO), |(mass, moment), &idx| {
+                        (
+                            mass + self.masses[idx],
+                            moment + self.positions[idx] * self.masses[idx],
+                        )
+                    });
+
+            self.set_mass(node, mass, moment);
+        }
+    }
+
+    fn set_mass(&mut self, node: usize, mass: f32, moment: Point) {
+        let node = &mut self.nodes[node];
+
+        node.mass = mass;
+
+        if mass > 0.0 {
+            node.center_of_mass = moment / mass;
+        }
+    }
+
+    /// Acceleration on body `idx`, where `pull(a, b)` is the acceleration at `a` due to a unit mass at `b`
+    /// and `separation(a, b)` is the displacement `pull` measures between them.
+    ///
+    /// Nodes whose size over distance falls below `theta` are treated as a single mass;
+    /// `theta = 0` reproduces the exact pairwise sum. On a wrapping world, nodes straddling
+    /// the seam as seen from the body are always opened, since their bodies are pulled through different images
+    pub fn acceleration<S, F>(&self, idx: usize, theta: f32, separation: S, pull: F) -> Velocity
+    where
+        S: Fn(Point, Point) -> Velocity,
+        F: Fn(Point, Point) -> Velocity,
+    {
+        let pos = self.positions[idx];
+        let theta2 = theta * theta;
+
+        // how far `separation` moved a point from where it really is, a whole world size whenever it wrapped
+        let image = |point: Point| separation(pos, point) - (point - pos);
+
+        let mut accel = Velocity::ZERO;
+        let mut stack = vec![0];
+
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+
+            if node.mass == 0.0 {
+                continue;
+            }
+
+            if node.children == 0 {
+                for &other in &self.order[node.bodies.clone()] {
+                    if other != idx {
+                        accel += pull(pos, self.positions[other]) * self.masses[other];
+                    }
+                }
+
+                continue;
+            }
+
+            let size = node.half * 2.0;
+            let distance2 = separation(pos, node.center_of_mass).length_squared();
+
+            let one_image = (image(node.center - node.half) - image(node.center + node.half))
+                .abs()
+                .max_element()
+                < node.half;
+
+            if one_image
+                && size * size < theta2 * distance2
+                && !node.contains(pos - image(node.center))
+            {
+                accel += pull(pos, node.center_of_mass) * node.mass;
+            } else {
+                stack.extend(node.children..node.children + 4);
+            }
+        }
+
+        accel
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(a: Point, b: Point) -> Velocity {
+        b - a
+    }
+
+    fn newton(a: Point, b: Point) -> Velocity {
+        let d = b - a;
+        let r2 = d.length_squared();
+
+        d / (r2 * r2.sqrt())
+    }
+
+    // small deterministic LCG, good enough to scatter test bodies
+    fn scatter(count: usize) -> (Vec<Point>, Vec<f32>) {
+        let mut state: u32 = 0x2545_f491;
+        let mut next = || {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (state >> 8) as f32 / (1 << 24) as f32
+        };
+
+        (0..count)
+            .map(|_| {
+                (
+                    Point::new(next() * 256.0, next() * 256.0),
+                    1.0 + next() * 99.0,
+                )
+            })
+            .unzip()
+    }
+
+    fn exact(positions: &[Point], masses: &[f32]) -> Vec<Velocity> {
+        positions
+            .iter()
+            .enumerate()
+            .map(|(idx, &pos)| {
+                positions
+                    .iter()
+                    .zip(masses)
+                    .enumerate()
+                    .filter(|(other, _)| *other != idx)
+                    .fold(Velocity::ZERO, |accel, (_, (&other, &mass))| {
+                        accel + newton(pos, other) * mass
+                    })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn zero_theta_is_exact() {
+        let (positions, masses) = scatter(200);
+        let tree = QuadTree::new(&positions, &masses);
+
+        for (idx, expected) in exact(&positions, &masses).into_iter().enumerate() {
+            let accel = tree.acceleration(idx, 0.0, plain, newton);
+
+            assert!((accel - expected).length() <= expected.length() * 1e-4);
+        }
+    }
+
+    #[test]
+    fn matches_pairwise_within_tolerance() {
+        let (positions, masses) = scatter(1000);
+        let tree = QuadTree::new(&positions, &masses);
+
+        let expected = exact(&positions, &masses);
+
+        // individual accelerations can nearly cancel out, so measure errors against the typical magnitude
+        let rms = (expected.iter().map(|a| a.length_squared()).sum::<f32>()
+            / expected.len() as f32)
+            .sqrt();
+
+        let mut total_error = 0.0;
+
+        for (idx, expected) in expected.into_iter().enumerate() {
+            let accel = tree.acceleration(idx, 0.5, plain, newton);
+            let error = (accel - expected).length() / rms;
+
+            assert!(error < 0.02, "body {} off by {:.2}%", idx, error * 100.0);
+            total_error += error;
+        }
+
+        assert!(total_error / (positions.len() as f32) < 0.005);
+    }
+
+    #[test]
+    fn coincident_bodies_terminate() {
+        let positions = vec![Point::new(10.0, 10.0); 8];
+        let masses = vec![1.0; 8];
+
+        let tree = QuadTree::new(&positions, &masses);
+
+        assert_eq!(tree.nodes.len(), 1);
+        assert_eq!(
+            tree.acceleration(0, 0.5, plain, |_, _| Velocity::ONE),
+            Velocity::splat(7.0)
+        );
+    }
+}
@@ -0,0 +1,374 @@
+// MIT License
+
+// Copyright (c) 2022 AnonmousDapper
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use log::info;
+
+use crate::{
+    canvas::{Canvas, ExportError},
+    pipeline::PostSettings,
+};
+
+/// Shortest GIF frame delay, in hundredths of a second; viewers slow anything quicker right down
+const GIF_MIN_DELAY: u32 = 2;
+
+/// NeuQuant sampling for GIF palettes: 1 is best, 30 is fastest
+const GIF_QUANTIZE_SPEED: i32 = 10;
+
+/// Rate recordings are played back at, matching a 60Hz display
+const FRAME_RATE: u32 = 60;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordFormat {
+    /// Looping animated GIF
+    Gif,
+
+    /// Numbered PNGs in a directory
+    Png,
+
+    /// Raw YUV 4:4:4 stream for ffmpeg; `-` writes to stdout
+    Y4m,
+}
+
+impl RecordFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Gif => "gif",
+            Self::Png => "png",
+            Self::Y4m => "y4m",
+        }
+    }
+}
+
+impl std::str::FromStr for RecordFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "gif" => Ok(Self::Gif),
+            "png" | "sequence" => Ok(Self::Png),
+            "y4m" => Ok(Self::Y4m),
+            _ => Err(format!(
+                "unknown recording format {:?}, expected gif, png or y4m",
+                value
+            )),
+        }
+    }
+}
+
+/// Centiseconds from the start of a recording to the given frame, to the nearest one
+fn gif_timestamp(frame: u32) -> u32 {
+    (frame * 100 + FRAME_RATE / 2) / FRAME_RATE
+}
+
+/// GIFs can't keep up with 60fps, so some frames are left out and the rest held for longer
+#[derive(Debug, Default)]
+struct GifTiming {
+    /// Next frame that goes into the GIF
+    next: u32,
+
+    /// Centiseconds of the recording the GIF covers so far
+    shown: u32,
+}
+
+impl GifTiming {
+    /// How long to show `frame` for, or `None` if it is skipped
+    fn delay(&mut self, frame: u32) -> Option<u16> {
+        if frame < self.next {
+            return None;
+        }
+
+        // hold it until the first later frame at least the minimum delay away
+        self.next = (frame + 1..)
+            .find(|&later| gif_timestamp(later) >= self.shown + GIF_MIN_DELAY)
+            .unwrap_or(u32::MAX);
+
+        let delay = gif_timestamp(self.next) - self.shown;
+        self.shown += delay;
+
+        Some(delay as u16)
+    }
+}
+
+enum Sink {
+    Gif(gif::Encoder<BufWriter<File>>, GifTiming),
+    Sequence { dir: PathBuf },
+    Y4m(Box<dyn Write>),
+}
+
+/// Encodes successive canvas frames to disk
+pub struct Recorder {
+    sink: Sink,
+    path: PathBuf,
+    width: u32,
+    height: u32,
+    frames: u32,
+    limit: Option<u32>,
+}
+
+impl Recorder {
+    /// Start a recording of `width` x `height` frames.
+    ///
+    /// `path` is a file for GIF and Y4M, and a directory (created if needed) for PNG sequences
+    pub fn new<P: AsRef<Path>>(
+        path: P,
+        format: RecordFormat,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, ExportError> {
+        let path = path.as_ref().to_owned();
+
+        let io_error = |source| ExportError::Io {
+            path: path.clone(),
+            source,
+        };
+
+        let sink = match format {
+            RecordFormat::Gif => {
+                let (gif_width, gif_height) = match (u16::try_from(width), u16::try_from(height)) {
+                    (Ok(w), Ok(h)) => (w, h),
+                    _ => {
+                        return Err(io_error(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "GIFs are limited to 65535 pixels a side",
+                        )))
+                    }
+                };
+
+                let file = BufWriter::new(File::create(&path).map_err(io_error)?);
+
+                let gif_error = |source| ExportError::Gif {
+                    path: path.clone(),
+                    source,
+                };
+
+                let mut encoder =
+                    gif::Encoder::new(file, gif_width, gif_height, &[]).map_err(gif_error)?;
+                encoder
+                    .set_repeat(gif::Repeat::Infinite)
+                    .map_err(gif_error)?;
+
+                Sink::Gif(encoder, GifTiming::default())
+            }
+            RecordFormat::Png => {
+                fs::create_dir_all(&path).map_err(io_error)?;
+
+                Sink::Sequence { dir: path.clone() }
+            }
+            RecordFormat::Y4m => {
+                let mut out: Box<dyn Write> = if path.as_os_str() == "-" {
+                    Box::new(BufWriter::new(io::stdout()))
+                } else {
+                    Box::new(BufWriter::new(File::create(&path).map_err(io_error)?))
+                };
+
+                writeln!(
+                    out,
+                    "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+                    width, height, FRAME_RATE
+                )
+                .map_err(io_error)?;
+
+                Sink::Y4m(out)
+            }
+        };
+
+        Ok(Self {
+            sink,
+            path,
+            width,
+            height,
+            frames: 0,
+            limit: None,
+        })
+    }
+
+    /// Stop accepting frames once `limit` have been recorded
+    pub fn with_limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Number of frames recorded so far
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    /// Whether the frame limit has been reached
+    pub fn is_full(&self) -> bool {
+        self.limit.is_some_and(|limit| self.frames >= limit)
+    }
+
+    /// Append a frame, returning false if the limit was already reached.
+    ///
+    /// The canvas has to be the size the recording was started with, and is tonemapped with `settings`.
+    pub fn record(
+        &mut self,
+        canvas: &Canvas,
+        settings: &PostSettings,
+    ) -> Result<bool, ExportError> {
+        if self.is_full() {
+            return Ok(false);
+        }
+
+        let size = (canvas.width(), canvas.height());
+
+        if size != (self.width, self.height) {
+            return Err(ExportError::FrameSize {
+                path: self.path.clone(),
+                expected: (self.width, self.height),
+                found: size,
+            });
+        }
+
+        match &mut self.sink {
+            Sink::Gif(encoder, timing) => {
+                if let Some(delay) = timing.delay(self.frames) {
+                    let mut frame = gif::Frame::from_rgb_speed(
+                        self.width as u16,
+                        self.height as u16,
+                        &canvas.to_rgb8(settings),
+                        GIF_QUANTIZE_SPEED,
+                    );
+                    frame.delay = delay;
+
+                    encoder
+                        .write_frame(&frame)
+                        .map_err(|source| ExportError::Gif {
+                            path: self.path.clone(),
+                            source,
+                        })?;
+                }
+            }
+            Sink::Sequence { dir } => {
+                canvas.save_png(dir.join(format!("frame-{:05}.png", self.frames)), settings)?;
+            }
+            Sink::Y4m(out) => {
+                out.write_all(b"FRAME\n")
+                    .and_then(|_| out.write_all(&to_yuv444(&canvas.to_rgb8(settings))))
+                    .map_err(|source| ExportError::Io {
+                        path: self.path.clone(),
+                        source,
+                    })?;
+            }
+        }
+
+        self.frames += 1;
+
+        Ok(true)
+    }
+
+    /// Flush everything out and close the recording
+    pub fn finish(self) -> Result<(), ExportError> {
+        let path = self.path;
+
+        let result = match self.sink {
+            Sink::Gif(encoder, _) => encoder.into_inner().and_then(|mut file| file.flush()),
+            Sink::Sequence { .. } => Ok(()),
+            Sink::Y4m(mut out) => out.flush(),
+        };
+
+        result.map_err(|source| ExportError::Io {
+            path: path.clone(),
+            source,
+        })?;
+
+        info!("recorded {} frames to {}", self.frames, path.display());
+
+        Ok(())
+    }
+}
+
+/// Packed 8-bit RGB to planar BT.601 limited-range Y'CbCr, which is what ffmpeg assumes for Y4M
+fn to_yuv444(rgb: &[u8]) -> Vec<u8> {
+    let pixels = rgb.len() / 3;
+    let mut planes = vec![0; pixels * 3];
+
+    for (idx, pixel) in rgb.chunks_exact(3).enumerate() {
+        let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(|c| c as f32);
+
+        let y = 16.0 + 0.257 * r + 0.504 * g + 0.098 * b;
+        let cb = 128.0 - 0.148 * r - 0.291 * g + 0.439 * b;
+        let cr = 128.0 + 0.439 * r - 0.368 * g - 0.071 * b;
+
+        planes[idx] = y.round() as u8;
+        planes[pixels + idx] = cb.round() as u8;
+        planes[pixels * 2 + idx] = cr.round() as u8;
+    }
+
+    planes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recording_stops_at_the_limit() {
+        let dir = std::env::temp_dir().join(format!("pixie-frames-{}", std::process::id()));
+        let settings = PostSettings::default();
+
+        let mut recorder = Recorder::new(&dir, RecordFormat::Png, 4, 3)
+            .unwrap()
+            .with_limit(2);
+
+        let canvas = Canvas::with_size(4, 3);
+
+        assert!(recorder.record(&canvas, &settings).unwrap());
+        assert!(!recorder.is_full());
+        assert!(recorder.record(&canvas, &settings).unwrap());
+        assert!(recorder.is_full());
+        assert!(!recorder.record(&canvas, &settings).unwrap());
+
+        assert_eq!(recorder.frames(), 2);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        // and a frame of the wrong size is refused rather than written
+        let mut recorder = Recorder::new(&dir, RecordFormat::Png, 4, 3).unwrap();
+
+        assert!(matches!(
+            recorder.record(&Canvas::with_size(3, 4), &settings),
+            Err(ExportError::FrameSize { found: (3, 4), .. })
+        ));
+        assert_eq!(recorder.frames(), 0);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn gifs_keep_real_time() {
+        let mut timing = GifTiming::default();
+
+        let delays: Vec<u16> = (0..FRAME_RATE * 3)
+            .filter_map(|frame| timing.delay(frame))
+            .collect();
+
+        assert!(delays.iter().all(|&delay| delay as u32 >= GIF_MIN_DELAY));
+
+        // three seconds of frames play for three seconds, give or take the last frame's hold
+        let total: u32 = delays.iter().map(|&delay| delay as u32).sum();
+        assert!((300..=303).contains(&total), "{total}cs");
+    }
+
+    #[test]
+    fn yuv_matches_bt601() {
+        let rgb = [0, 0, 0, 255, 255, 255, 255, 0, 0, 0, 0, 255];
+
+        let planes = to_yuv444(&rgb);
+
+        // black and white land on the limited-range ends, with neutral chroma
+        assert_eq!(planes[..4], [16, 235, 82, 41]);
+        assert_eq!(planes[4..8], [128, 128, 90, 240]);
+        assert_eq!(planes[8..], [128, 128, 240, 110]);
+    }
+}
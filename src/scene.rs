@@ -0,0 +1,415 @@
+// MIT License
+
+// Copyright (c) 2022 AnonmousDapper
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camera::Camera,
+    constraint::Constraint,
+    force::{default_forces, ForceConfig},
+    pipeline::{default_post_passes, PassConfig, PostSettings, RenderConfig},
+    simulation::{
+        default_resolution, default_world_size, Boundary, CollisionMode, ColorModel, ForceSolver,
+        Integrator, Particle, Point, ThermalSettings,
+    },
+};
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+fn default_theta() -> f32 {
+    0.5
+}
+
+fn default_fragment_energy() -> f32 {
+    1e6
+}
+
+fn default_blackbody_blend() -> f32 {
+    0.5
+}
+
+fn default_timestep() -> f32 {
+    1.0
+}
+
+fn default_step_rate() -> f32 {
+    60.0
+}
+
+fn default_max_substeps() -> u32 {
+    64
+}
+
+fn default_constraint_iterations() -> u32 {
+    8
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// A scene file: simulator settings plus the particles to populate it with.
+///
+/// ```toml
+/// physics_scale = 1.0
+/// integrator = "velocity_verlet"
+///
+/// [[particle]]
+/// name = "Sol"
+/// pos = [80.0, 80.0]
+/// mass = 7e9
+/// ```
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scene {
+    #[serde(default = "default_scale")]
+    #[serde(alias = "scale")]
+    pub physics_scale: f32,
+
+    /// Size of the world in scene units; the default matches the canvas one-to-one
+    #[serde(default = "default_world_size")]
+    #[serde(alias = "world")]
+    pub world_size: Point,
+
+    /// Canvas size in pixels, `[width, height]`
+    #[serde(default = "default_resolution")]
+    pub resolution: (u32, u32),
+
+    /// Where particle hues come from
+    #[serde(default)]
+    pub color_model: ColorModel,
+
+    /// Share of blackbody color when blending it with the authored color
+    #[serde(default = "default_blackbody_blend")]
+    pub blackbody_blend: f32,
+
+    #[serde(default)]
+    pub integrator: Integrator,
+
+    #[serde(default)]
+    pub solver: ForceSolver,
+
+    /// Barnes-Hut opening angle
+    #[serde(default = "default_theta")]
+    pub theta: f32,
+
+    #[serde(default)]
+    pub boundary: Boundary,
+
+    #[serde(default)]
+    #[serde(alias = "collisions")]
+    pub collision_mode: CollisionMode,
+
+    /// Impact energy needed to shatter a particle when fragmenting
+    #[serde(default = "default_fragment_energy")]
+    pub fragment_energy: f32,
+
+    /// Plummer softening length for gravity
+    #[serde(default)]
+    pub softening: f32,
+
+    /// Simulation time covered by one step
+    #[serde(default = "default_timestep")]
+    #[serde(alias = "dt")]
+    pub timestep: f32,
+
+    /// Steps per real second at 1x speed
+    #[serde(default = "default_step_rate")]
+    pub step_rate: f32,
+
+    #[serde(default = "default_max_substeps")]
+    pub max_substeps: u32,
+
+    /// Position-based passes made over rods and pins each step
+    #[serde(default = "default_constraint_iterations")]
+    pub constraint_iterations: u32,
+
+    /// Set on snapshots, whose particles already have their derived properties worked out
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_false")]
+    pub resolved: bool,
+
+    // tables have to come last for the TOML serializer
+    /// Initial view; fits the whole world when left out
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub camera: Option<Camera>,
+
+    #[serde(default)]
+    pub post_settings: PostSettings,
+
+    /// Radiative cooling, heating and collision heat; off unless the scene turns it on
+    #[serde(default)]
+    pub thermal: ThermalSettings,
+
+    /// Forces acting on the particles, summed in order; just gravity when left out, and none at all for `force = []`
+    #[serde(default = "default_forces")]
+    #[serde(rename = "force", alias = "forces")]
+    pub forces: Vec<ForceConfig>,
+
+    /// Springs, rods and pins between named particles
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(rename = "constraint", alias = "constraints")]
+    pub constraints: Vec<Constraint>,
+
+    /// Post-processing chain, run in order
+    #[serde(default = "default_post_passes")]
+    pub post: Vec<PassConfig>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(rename = "particle", alias = "particles")]
+    pub particles: Vec<Particle>,
+}
+
+/// On-disk scene encodings, picked by file extension
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SceneFormat {
+    Toml,
+    Json,
+}
+
+impl SceneFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Self::Json,
+            _ => Self::Toml,
+        }
+    }
+}
+
+impl Scene {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SceneError> {
+        let path = path.as_ref();
+
+        let source = fs::read_to_string(path).map_err(|source| SceneError::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+
+        match SceneFormat::from_path(path) {
+            SceneFormat::Toml => source.parse().map_err(|source| SceneError::Parse {
+                path: path.to_owned(),
+                source,
+            }),
+            SceneFormat::Json => serde_json::from_str(&source).map_err(|source| SceneError::Json {
+                path: path.to_owned(),
+                source,
+            }),
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SceneError> {
+        let path = path.as_ref();
+
+        let contents = match SceneFormat::from_path(path) {
+            SceneFormat::Toml => self.to_toml().map_err(|source| SceneError::Serialize {
+                path: path.to_owned(),
+                source,
+            })?,
+            SceneFormat::Json => self.to_json().map_err(|source| SceneError::Json {
+                path: path.to_owned(),
+                source,
+            })?,
+        };
+
+        fs::write(path, contents).map_err(|source| SceneError::Io {
+            path: path.to_owned(),
+            source,
+        })
+    }
+
+    /// The post-processing this scene asks for
+    pub fn render_config(&self) -> RenderConfig {
+        RenderConfig {
+            passes: self.post.clone(),
+            settings: self.post_settings,
+        }
+    }
+
+    pub fn set_render_config(&mut self, config: RenderConfig) {
+        self.post = config.passes;
+        self.post_settings = config.settings;
+    }
+
+    pub fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        // an empty list is a plain `key = []`, which has to come before the tables. Going through a `Value`
+        // sorts those to the front, so cleared forces or post passes are still written out
+        toml::to_string(&toml::Value::try_from(self)?)
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self {
+            physics_scale: default_scale(),
+            world_size: default_world_size(),
+            resolution: default_resolution(),
+            color_model: ColorModel::default(),
+            blackbody_blend: default_blackbody_blend(),
+            integrator: Integrator::default(),
+            solver: ForceSolver::default(),
+            theta: default_theta(),
+            boundary: Boundary::default(),
+            collision_mode: CollisionMode::default(),
+            fragment_energy: default_fragment_energy(),
+            softening: 0.0,
+            timestep: default_timestep(),
+            step_rate: default_step_rate(),
+            max_substeps: default_max_substeps(),
+            constraint_iterations: default_constraint_iterations(),
+            resolved: false,
+            camera: None,
+            post_settings: PostSettings::default(),
+            thermal: ThermalSettings::default(),
+            forces: default_forces(),
+            constraints: Vec::new(),
+            post: default_post_passes(),
+            particles: Vec::new(),
+        }
+    }
+}
+
+impl FromStr for Scene {
+    type Err = toml::de::Error;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        toml::from_str(source)
+    }
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    Serialize {
+        path: PathBuf,
+        source: toml::ser::Error,
+    },
+    Json {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+}
+
+impl SceneError {
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Io { path, .. }
+            | Self::Parse { path, .. }
+            | Self::Serialize { path, .. }
+            | Self::Json { path, .. } => path,
+        }
+    }
+
+    /// 1-based line and column of a parse error, if known
+    pub fn line_col(&self) -> Option<(usize, usize)> {
+        match self {
+            Self::Parse { source, .. } => source.line_col().map(|(line, col)| (line + 1, col + 1)),
+            Self::Json { source, .. } if source.line() > 0 => {
+                Some((source.line(), source.column()))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => {
+                write!(f, "failed to access scene {}: {}", path.display(), source)
+            }
+            // toml and json errors already name the offending key and position
+            Self::Parse { path, source } => {
+                write!(f, "invalid scene {}: {}", path.display(), source)
+            }
+            Self::Json { path, source } => {
+                write!(f, "invalid scene {}: {}", path.display(), source)
+            }
+            Self::Serialize { path, source } => {
+                write!(
+                    f,
+                    "failed to serialize scene {}: {}",
+                    path.display(),
+                    source
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Parse { source, .. } => Some(source),
+            Self::Serialize { source, .. } => Some(source),
+            Self::Json { source, .. } => Some(source),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shipped_scenes() -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = fs::read_dir("scenes")
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension() == Some("toml".as_ref()))
+            .collect();
+
+        paths.sort();
+        assert!(!paths.is_empty());
+
+        paths
+    }
+
+    #[test]
+    fn shipped_scenes_round_trip() {
+        let dir = std::env::temp_dir().join(format!("pixie-scenes-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        for path in shipped_scenes() {
+            let scene = Scene::load(&path).unwrap();
+            let toml = scene.to_toml().unwrap();
+            let json = scene.to_json().unwrap();
+
+            // saving and loading again has to give back exactly what was saved, in either format
+            for (ext, expected) in [("toml", &toml), ("json", &json)] {
+                let copy = dir.join(path.with_extension(ext).file_name().unwrap());
+
+                scene.save(&copy).unwrap();
+                let reloaded = Scene::load(&copy).unwrap();
+
+                assert_eq!(&reloaded.to_toml().unwrap(), &toml, "{}", copy.display());
+                assert_eq!(&reloaded.to_json().unwrap(), &json, "{}", copy.display());
+                assert_eq!(&fs::read_to_string(&copy).unwrap(), expected);
+            }
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
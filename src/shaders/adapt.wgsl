@@ -0,0 +1,33 @@
+// Target exposure from the average luminance; blended with last frame's value to adapt over time
+
+struct PostSettings {
+    exposure: f32;
+    gamma: f32;
+    bloom_threshold: f32;
+    trail_decay: f32;
+    trail_persistence: f32;
+    white_point: f32;
+    tonemapper: u32;
+    auto_exposure: u32;
+    min_ev: f32;
+    max_ev: f32;
+    bloom_intensity: f32;
+};
+
+[[group(0), binding(0)]]
+var average: texture_2d<f32>;
+
+[[group(1), binding(0)]]
+var<uniform> settings: PostSettings;
+
+[[stage(fragment)]]
+fn main([[builtin(position)]] position: vec4<f32>) -> [[location(0)]] vec4<f32> {
+    let mean = textureLoad(average, vec2<i32>(0, 0), 0);
+
+    // keep the current exposure while nothing is on screen
+    if (mean.g <= 0.0) {
+        discard;
+    }
+
+    return vec4<f32>(clamp(mean.r, settings.min_ev, settings.max_ev), 0.0, 0.0, 1.0);
+}
@@ -9,6 +9,23 @@ var surface: texture_2d<f32>;
 [[group(0), binding(2)]]
 var mix_surface: texture_2d<f32>;
 
+struct PostSettings {
+    exposure: f32;
+    gamma: f32;
+    bloom_threshold: f32;
+    trail_decay: f32;
+    trail_persistence: f32;
+    white_point: f32;
+    tonemapper: u32;
+    auto_exposure: u32;
+    min_ev: f32;
+    max_ev: f32;
+    bloom_intensity: f32;
+};
+
+[[group(1), binding(0)]]
+var<uniform> settings: PostSettings;
+
 
 [[stage(fragment)]]
 fn main([[location(0)]] coords: vec2<f32>) -> [[location(0)]] vec4<f32> {
@@ -16,6 +33,6 @@ fn main([[location(0)]] coords: vec2<f32>) -> [[location(0)]] vec4<f32> {
 
     let mix = textureSample(mix_surface, tex_sampler, coords);
 
-    return frame + mix + (1.0 - frame.a);
+    return frame + vec4<f32>(mix.rgb * settings.bloom_intensity, mix.a) + (1.0 - frame.a);
     //return vec4<f32>(frame + mix, 1.0);
 }
\ No newline at end of file
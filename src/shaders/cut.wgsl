@@ -6,6 +6,23 @@ var tex_sampler: sampler;
 [[group(0), binding(1)]]
 var surface: texture_2d<f32>;
 
+struct PostSettings {
+    exposure: f32;
+    gamma: f32;
+    bloom_threshold: f32;
+    trail_decay: f32;
+    trail_persistence: f32;
+    white_point: f32;
+    tonemapper: u32;
+    auto_exposure: u32;
+    min_ev: f32;
+    max_ev: f32;
+    bloom_intensity: f32;
+};
+
+[[group(1), binding(0)]]
+var<uniform> settings: PostSettings;
+
 struct Fragment {
     [[location(0)]] color: vec4<f32>;
     [[location(1)]] cut: vec4<f32>;
@@ -18,7 +35,7 @@ fn main([[location(0)]] coords: vec2<f32>) -> Fragment {
 
     out.color = color;
 
-    out.cut = vec4<f32>(color.rgb / vec3<f32>(1.25), min(color.a, 0.95));
+    out.cut = vec4<f32>(color.rgb / vec3<f32>(settings.trail_decay), min(color.a, settings.trail_persistence));
 
     return out;
 }
\ No newline at end of file
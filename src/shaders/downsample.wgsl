@@ -0,0 +1,27 @@
+// Halve a level of the exposure mip chain
+//
+// r holds the mean log luminance of the lit pixels below, g the fraction of them that were lit
+
+[[group(0), binding(0)]]
+var level: texture_2d<f32>;
+
+[[stage(fragment)]]
+fn main([[builtin(position)]] position: vec4<f32>) -> [[location(0)]] vec4<f32> {
+    let size = textureDimensions(level) - vec2<i32>(1);
+    let base = vec2<i32>(position.xy) * 2;
+
+    let a = textureLoad(level, min(base, size), 0).rg;
+    let b = textureLoad(level, min(base + vec2<i32>(1, 0), size), 0).rg;
+    let c = textureLoad(level, min(base + vec2<i32>(0, 1), size), 0).rg;
+    let d = textureLoad(level, min(base + vec2<i32>(1, 1), size), 0).rg;
+
+    let lit = a.g + b.g + c.g + d.g;
+
+    if (lit <= 0.0) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+
+    let mean = (a.r * a.g + b.r * b.g + c.r * c.g + d.r * d.g) / lit;
+
+    return vec4<f32>(mean, lit * 0.25, 0.0, 1.0);
+}
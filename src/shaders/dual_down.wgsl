@@ -0,0 +1,20 @@
+// Dual-filter bloom downsample: centre plus four diagonal taps, into a half-size level
+
+[[group(0), binding(0)]]
+var tex_sampler: sampler;
+
+[[group(0), binding(1)]]
+var surface: texture_2d<f32>;
+
+[[stage(fragment)]]
+fn main([[location(0)]] coords: vec2<f32>) -> [[location(0)]] vec4<f32> {
+    let half_texel = 0.5 / vec2<f32>(textureDimensions(surface));
+
+    var sum: vec3<f32> = textureSample(surface, tex_sampler, coords).rgb * 4.0;
+    sum = sum + textureSample(surface, tex_sampler, coords - half_texel).rgb;
+    sum = sum + textureSample(surface, tex_sampler, coords + half_texel).rgb;
+    sum = sum + textureSample(surface, tex_sampler, coords + vec2<f32>(half_texel.x, -half_texel.y)).rgb;
+    sum = sum + textureSample(surface, tex_sampler, coords - vec2<f32>(half_texel.x, -half_texel.y)).rgb;
+
+    return vec4<f32>(sum / 8.0, 1.0);
+}
@@ -0,0 +1,23 @@
+// Dual-filter bloom upsample: a tent of eight taps around the point, added on to the larger level
+
+[[group(0), binding(0)]]
+var tex_sampler: sampler;
+
+[[group(0), binding(1)]]
+var surface: texture_2d<f32>;
+
+[[stage(fragment)]]
+fn main([[location(0)]] coords: vec2<f32>) -> [[location(0)]] vec4<f32> {
+    let half_texel = 0.5 / vec2<f32>(textureDimensions(surface));
+
+    var sum: vec3<f32> = textureSample(surface, tex_sampler, coords + vec2<f32>(-half_texel.x * 2.0, 0.0)).rgb;
+    sum = sum + textureSample(surface, tex_sampler, coords + vec2<f32>(half_texel.x * 2.0, 0.0)).rgb;
+    sum = sum + textureSample(surface, tex_sampler, coords + vec2<f32>(0.0, -half_texel.y * 2.0)).rgb;
+    sum = sum + textureSample(surface, tex_sampler, coords + vec2<f32>(0.0, half_texel.y * 2.0)).rgb;
+    sum = sum + textureSample(surface, tex_sampler, coords + half_texel).rgb * 2.0;
+    sum = sum + textureSample(surface, tex_sampler, coords - half_texel).rgb * 2.0;
+    sum = sum + textureSample(surface, tex_sampler, coords + vec2<f32>(half_texel.x, -half_texel.y)).rgb * 2.0;
+    sum = sum + textureSample(surface, tex_sampler, coords - vec2<f32>(half_texel.x, -half_texel.y)).rgb * 2.0;
+
+    return vec4<f32>(sum / 12.0, 1.0);
+}
@@ -6,23 +6,136 @@ var tex_sampler: sampler;
 [[group(0), binding(1)]]
 var surface: texture_2d<f32>;
 
-let gamma: f32 = 2.2;
-let exposure: f32 = 1.0;
+struct PostSettings {
+    exposure: f32;
+    gamma: f32;
+    bloom_threshold: f32;
+    trail_decay: f32;
+    trail_persistence: f32;
+    white_point: f32;
+    tonemapper: u32;
+    auto_exposure: u32;
+    min_ev: f32;
+    max_ev: f32;
+    bloom_intensity: f32;
+};
+
+[[group(1), binding(0)]]
+var<uniform> settings: PostSettings;
+
+// adapted log2 luminance, from the exposure pass
+[[group(0), binding(2)]]
+var adapted: texture_2d<f32>;
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+fn reinhard_extended(color: vec3<f32>) -> vec3<f32> {
+    let lum = luminance(color);
+
+    if (lum <= 0.0) {
+        return vec3<f32>(0.0);
+    }
+
+    let white2 = settings.white_point * settings.white_point;
+    let mapped = lum * (1.0 + lum / white2) / (1.0 + lum);
+
+    return color * (mapped / lum);
+}
+
+// Krzysztof Narkowicz's fit
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    let x = color * 0.6;
+
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// polynomial fit of the AgX base contrast curve
+fn agx_contrast(x: vec3<f32>) -> vec3<f32> {
+    let x2 = x * x;
+    let x4 = x2 * x2;
+
+    return 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+}
+
+fn agx(color: vec3<f32>) -> vec3<f32> {
+    let inset = mat3x3<f32>(
+        vec3<f32>(0.842479062253094, 0.0423282422610123, 0.0423756549057051),
+        vec3<f32>(0.0784335999999992, 0.878468636469772, 0.0784336),
+        vec3<f32>(0.0792237451477643, 0.0791661274605434, 0.879142973793104),
+    );
+
+    let outset = mat3x3<f32>(
+        vec3<f32>(1.19687900512017, -0.0528968517574562, -0.0529716355144438),
+        vec3<f32>(-0.0980208811401368, 1.15190312990417, -0.0980434501171241),
+        vec3<f32>(-0.0990297440797205, -0.0989611768448433, 1.15107367264116),
+    );
+
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+
+    var x: vec3<f32> = inset * max(color, vec3<f32>(1e-10));
+    x = (clamp(log2(x), vec3<f32>(min_ev), vec3<f32>(max_ev)) - min_ev) / (max_ev - min_ev);
+    x = outset * agx_contrast(x);
+
+    // the curve's output is display-encoded, bring it back to linear
+    return pow(max(x, vec3<f32>(0.0)), vec3<f32>(2.2));
+}
+
+// black below -6 EV, then blue, cyan, green, yellow, red and white a couple of stops apart
+fn false_color(color: vec3<f32>) -> vec3<f32> {
+    let lum = luminance(color);
+
+    if (lum <= 0.0) {
+        return vec3<f32>(0.0);
+    }
+
+    let t = clamp((log2(lum) + 6.0) / 2.0, 0.0, 6.0);
+
+    if (t < 1.0) {
+        return mix(vec3<f32>(0.0), vec3<f32>(0.0, 0.0, 1.0), t);
+    } else if (t < 2.0) {
+        return mix(vec3<f32>(0.0, 0.0, 1.0), vec3<f32>(0.0, 1.0, 1.0), t - 1.0);
+    } else if (t < 3.0) {
+        return mix(vec3<f32>(0.0, 1.0, 1.0), vec3<f32>(0.0, 1.0, 0.0), t - 2.0);
+    } else if (t < 4.0) {
+        return mix(vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(1.0, 1.0, 0.0), t - 3.0);
+    } else if (t < 5.0) {
+        return mix(vec3<f32>(1.0, 1.0, 0.0), vec3<f32>(1.0, 0.0, 0.0), t - 4.0);
+    }
+
+    return mix(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(1.0), t - 5.0);
+}
 
 [[stage(fragment)]]
 fn main([[location(0)]] coords: vec2<f32>) -> [[location(0)]] vec4<f32> {
-    let hdr = textureSample(surface, tex_sampler, coords).rgb;
+    var exposure: f32 = settings.exposure;
 
-    // Reinhard
-    //var mapped: vec3<f32> = hdr / (hdr + vec3<f32>(1.0));
+    // bring the average lit pixel to `exposure`
+    if (settings.auto_exposure == 1u) {
+        exposure = exposure / exp2(textureLoad(adapted, vec2<i32>(0, 0), 0).r);
+    }
 
-    // exposure
-    var mapped: vec3<f32> = vec3<f32>(1.0) - exp(-hdr * exposure);
+    let hdr = textureSample(surface, tex_sampler, coords).rgb * exposure;
 
-    //mapped = pow(mapped, vec3<f32>(1.0 / gamma));
+    var mapped: vec3<f32>;
 
-    //mapped = pow(mapped, vec3<f32>(gamma));
+    if (settings.tonemapper == 1u) {
+        mapped = hdr / (hdr + vec3<f32>(1.0));
+    } else if (settings.tonemapper == 2u) {
+        mapped = reinhard_extended(hdr);
+    } else if (settings.tonemapper == 3u) {
+        mapped = aces(hdr);
+    } else if (settings.tonemapper == 4u) {
+        mapped = agx(hdr);
+    } else if (settings.tonemapper == 5u) {
+        mapped = false_color(hdr);
+    } else {
+        mapped = vec3<f32>(1.0) - exp(-hdr);
+    }
 
-    return vec4<f32>(mapped, 1.0);
+    mapped = pow(mapped, vec3<f32>(1.0 / settings.gamma));
 
-}
\ No newline at end of file
+    return vec4<f32>(mapped, 1.0);
+}
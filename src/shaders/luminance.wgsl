@@ -0,0 +1,18 @@
+// Log luminance of lit pixels, first level of the exposure mip chain
+
+[[group(0), binding(0)]]
+var surface: texture_2d<f32>;
+
+[[stage(fragment)]]
+fn main([[builtin(position)]] position: vec4<f32>) -> [[location(0)]] vec4<f32> {
+    let color = textureLoad(surface, vec2<i32>(position.xy), 0).rgb;
+
+    let lum = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+
+    // empty space would drag the average down to nothing, so it only counts towards coverage
+    if (lum <= 0.0001) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+
+    return vec4<f32>(log2(lum), 1.0, 0.0, 1.0);
+}
@@ -6,6 +6,23 @@ var tex_sampler: sampler;
 [[group(0), binding(1)]]
 var surface: texture_2d<f32>;
 
+struct PostSettings {
+    exposure: f32;
+    gamma: f32;
+    bloom_threshold: f32;
+    trail_decay: f32;
+    trail_persistence: f32;
+    white_point: f32;
+    tonemapper: u32;
+    auto_exposure: u32;
+    min_ev: f32;
+    max_ev: f32;
+    bloom_intensity: f32;
+};
+
+[[group(1), binding(0)]]
+var<uniform> settings: PostSettings;
+
 struct MultiFrag {
     [[location(0)]] color: vec4<f32>;
     [[location(1)]] bright_color: vec4<f32>;
@@ -21,7 +38,7 @@ fn main([[location(0)]] coords: vec2<f32>) -> MultiFrag {
 
     let bright: f32 = dot(sampled, vec3<f32>(0.2126, 0.7152, 0.0722));
 
-    if (bright > 1.0) {
+    if (bright > settings.bloom_threshold) {
         out.bright_color = vec4<f32>(sampled, 1.0);
     } else {
         out.bright_color = vec4<f32>(0.0, 0.0, 0.0, 0.0);
@@ -4,24 +4,27 @@
 
 #![allow(non_upper_case_globals)]
 
-use serde::Deserialize;
+use std::{path::Path, time::Duration};
+
+use log::{debug, warn};
+
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    canvas::{self, Canvas, Render, RgbaF16},
+    camera::Camera,
+    canvas::{Canvas, Render, RgbaF16, GAMMA},
+    constraint::{spring_force, Constraint, Link},
+    force::{default_forces, Force, ForceConfig, System},
+    scene::{Scene, SceneError},
+    soa::PointMasses,
     FB_HEIGHT, FB_WIDTH,
 };
 
 // Physics constants
 
-/// Gravitational constant (m³/s²/kg)
-const G: f32 = 6.67384e-11;
-
 /// Speed of light in vacuum (m/s)
 const c: f32 = 2.997_924_5e8;
 
-/// Charge on electron (C)
-const ε: f32 = 1.602_176_6e-19;
-
 /// Planck's constant (J/s)
 const h: f32 = 6.626_07e-34;
 
@@ -50,8 +53,119 @@ fn default_temp() -> f32 {
     288.0 // 188K ≈ 60°F
 }
 
-#[derive(Debug, Deserialize)]
+fn default_heat_capacity() -> f32 {
+    1000.0 // roughly rock, J/kg/K
+}
+
+/// Where a particle's hue comes from when it is drawn; brightness always follows its temperature
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorModel {
+    /// The particle's own `color`
+    #[default]
+    Authored,
+
+    /// Blackbody chromaticity at the particle's temperature, ignoring `color`
+    Blackbody,
+
+    /// A mix of the two, weighted by the simulator's blackbody blend
+    Blend,
+}
+
+// Multi-lobe Gaussian fits to the CIE 1931 2° colour matching functions
+// (Wyman, Sloan & Shirley 2013), as (weight, mean, σ below, σ above) in nm
+const CIE_X: [(f32, f32, f32, f32); 3] = [
+    (1.056, 599.8, 37.9, 31.0),
+    (0.362, 442.0, 16.0, 26.7),
+    (-0.065, 501.1, 20.4, 26.2),
+];
+const CIE_Y: [(f32, f32, f32, f32); 2] = [(0.821, 568.8, 46.9, 40.5), (0.286, 530.9, 16.3, 31.1)];
+const CIE_Z: [(f32, f32, f32, f32); 2] = [(1.217, 437.0, 11.8, 36.0), (0.681, 459.0, 26.0, 13.8)];
+
+fn cie_lobes(lobes: &[(f32, f32, f32, f32)], wavelength: f32) -> f32 {
+    lobes
+        .iter()
+        .map(|&(weight, mean, below, above)| {
+            let spread = if wavelength < mean { below } else { above };
+            weight * (-0.5 * ((wavelength - mean) / spread).powi(2)).exp()
+        })
+        .sum()
+}
+
+/// Linear sRGB chromaticity of a blackbody, scaled so the brightest channel is 1.
+///
+/// Planck's law is integrated against the CIE 1931 observer over the visible range, in log space
+/// so that cold bodies don't underflow. The fits lose accuracy in the far red, so anything
+/// colder than 500 K is drawn the same deep red
+pub fn blackbody(temperature: f32) -> [f32; 3] {
+    const STEPS: usize = 80;
+    const RANGE: (f32, f32) = (380.0, 780.0);
+
+    let temperature = temperature.max(500.0);
+    let wavelength = |idx: usize| RANGE.0 + (RANGE.1 - RANGE.0) * idx as f32 / STEPS as f32;
+
+    // ln of spectral radiance, less the constant 2hc² factor
+    let log_radiance = |nm: f32| {
+        let metres = nm * 1e-9;
+        let x = (h * c) / (metres * k * temperature);
+
+        -5.0 * metres.ln() - x - (-(-x).exp_m1()).ln()
+    };
+
+    let peak = (0..=STEPS)
+        .map(|idx| log_radiance(wavelength(idx)))
+        .fold(f32::NEG_INFINITY, f32::max);
+
+    let (x, y, z) = (0..=STEPS).fold((0.0, 0.0, 0.0), |(x, y, z), idx| {
+        let nm = wavelength(idx);
+        let radiance = (log_radiance(nm) - peak).exp();
+
+        (
+            x + radiance * cie_lobes(&CIE_X, nm),
+            y + radiance * cie_lobes(&CIE_Y, nm),
+            z + radiance * cie_lobes(&CIE_Z, nm),
+        )
+    });
+
+    let rgb = [
+        3.2406 * x - 1.5372 * y - 0.4986 * z,
+        -0.9689 * x + 1.8758 * y + 0.0415 * z,
+        0.0557 * x - 0.2040 * y + 1.0570 * z,
+    ]
+    .map(|channel| channel.max(0.0));
+
+    let max = rgb[0].max(rgb[1]).max(rgb[2]);
+
+    if max > 0.0 {
+        rgb.map(|channel| channel / max)
+    } else {
+        [1.0, 0.0, 0.0]
+    }
+}
+
+/// Handle to a particle in a `Simulator`, which stays valid however the others come and go.
+///
+/// Once its particle is removed (or merged into another), a handle never matches anything again
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ParticleId {
+    slot: u32,
+    generation: u32,
+}
+
+/// Where the particle holding a slot's handle currently sits in `Simulator::environment`
+#[derive(Clone, Copy, Debug, Default)]
+struct Slot {
+    generation: u32,
+    index: Option<usize>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct Particle {
+    /// Handed out by the simulator; new particles and fragments get theirs on the next reindex
+    #[serde(skip)]
+    id: Option<ParticleId>,
+
     #[serde(default)]
     name: String,
 
@@ -79,6 +193,16 @@ pub struct Particle {
 
     #[serde(default)]
     density: f32,
+
+    /// Specific heat (J/kg/K); 0 picks the default
+    #[serde(default)]
+    #[serde(alias = "cp")]
+    heat_capacity: f32,
+
+    /// Electric charge (C)
+    #[serde(default)]
+    #[serde(alias = "q")]
+    charge: f32,
 }
 
 impl Particle {
@@ -92,6 +216,7 @@ impl Particle {
         density: f32,
     ) -> Self {
         let mut part = Self {
+            id: None,
             name: String::new(),
             position,
             velocity,
@@ -100,6 +225,8 @@ impl Particle {
             size,
             mass,
             density,
+            heat_capacity: 0.0,
+            charge: 0.0,
         };
 
         part.resolve_properties();
@@ -107,6 +234,11 @@ impl Particle {
         part
     }
 
+    /// Handle in the simulator holding this particle, once it has been added to one
+    pub fn id(&self) -> Option<ParticleId> {
+        self.id
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -116,6 +248,17 @@ impl Particle {
         self
     }
 
+    pub fn with_charge(mut self, charge: f32) -> Self {
+        self.charge = charge;
+        self
+    }
+
+    pub fn with_heat_capacity(mut self, heat_capacity: f32) -> Self {
+        self.heat_capacity = heat_capacity;
+        self.resolve_properties();
+        self
+    }
+
     pub fn resolve_properties(&mut self) {
         self.temperature = if self.temperature == 0.0 {
             default_temp()
@@ -140,6 +283,10 @@ impl Particle {
         self.size = size;
         self.density = density;
         self.mass = mass;
+
+        if self.heat_capacity == 0.0 {
+            self.heat_capacity = default_heat_capacity();
+        }
     }
 
     pub fn position(&self) -> Point {
@@ -150,16 +297,9 @@ impl Particle {
         &mut self.position
     }
 
-    pub fn wrap_position(&mut self) {
-        self.position.x = self.position.x.rem_euclid(FB_WIDTH as f32);
-        self.position.y = self.position.y.rem_euclid(FB_HEIGHT as f32);
-    }
-
-    pub fn normalize_position(&self) -> canvas::Point {
-        let x = self.position.x.round() as canvas::Size;
-        let y = self.position.y.round() as canvas::Size;
-
-        (x.clamp(0, FB_WIDTH - 1), y.clamp(0, FB_HEIGHT - 1))
+    pub fn wrap_position(&mut self, world_size: Point) {
+        self.position.x = self.position.x.rem_euclid(world_size.x);
+        self.position.y = self.position.y.rem_euclid(world_size.y);
     }
 
     pub fn velocity(&self) -> Velocity {
@@ -174,16 +314,31 @@ impl Particle {
         self.color
     }
 
-    pub fn render_color(&self) -> RgbaF16 {
+    /// Drawing color, with the hue picked by `model`; `blend` is the blackbody share for `ColorModel::Blend`
+    pub fn render_color(&self, model: ColorModel, blend: f32) -> RgbaF16 {
         let l = (((4.0 * π * σ) * (self.size * 50.0).powi(2) * self.temperature.powi(4))
             / 1000000.0)
             .log(300.0);
 
-        RgbaF16::new(
-            l * self.color.0 as f32 / 255.0,
-            l * self.color.1 as f32 / 255.0,
-            l * self.color.2 as f32 / 255.0,
-        )
+        let authored = || {
+            [self.color.0, self.color.1, self.color.2]
+                .map(|channel| (channel as f32 / 255.0).powf(GAMMA))
+        };
+
+        let linear = match model {
+            ColorModel::Authored => authored(),
+            ColorModel::Blackbody => blackbody(self.temperature),
+            ColorModel::Blend => {
+                let blend = blend.clamp(0.0, 1.0);
+                let (authored, hot) = (authored(), blackbody(self.temperature));
+
+                [0, 1, 2].map(|idx| authored[idx] + (hot[idx] - authored[idx]) * blend)
+            }
+        };
+
+        let l = l.powf(GAMMA);
+
+        RgbaF16::new_raw(l * linear[0], l * linear[1], l * linear[2], 1.0)
     }
 
     pub fn temperature(&self) -> f32 {
@@ -194,10 +349,6 @@ impl Particle {
         self.size
     }
 
-    pub fn normalize_size(&self) -> canvas::Size {
-        self.size.round() as canvas::Size
-    }
-
     pub fn mass(&self) -> f32 {
         self.mass
     }
@@ -205,45 +356,370 @@ impl Particle {
     pub fn density(&self) -> f32 {
         self.density
     }
+
+    pub fn heat_capacity(&self) -> f32 {
+        self.heat_capacity
+    }
+
+    pub fn charge(&self) -> f32 {
+        self.charge
+    }
+
+    /// Energy needed to warm the whole particle by one kelvin
+    fn thermal_mass(&self) -> f32 {
+        self.mass * self.heat_capacity
+    }
+
+    /// Collision radius, matching the drawn size
+    pub fn radius(&self) -> f32 {
+        self.size * 0.5
+    }
+
+    /// Whether the two touch, measured the shortest way round the world
+    fn overlaps(&self, other: &Particle, boundary: Boundary, world_size: Point) -> bool {
+        boundary
+            .separation(self.position, other.position, world_size)
+            .length()
+            < self.radius() + other.radius()
+    }
+
+    fn momentum(&self) -> Velocity {
+        self.velocity * self.mass
+    }
+
+    /// Merge `other` into this particle, conserving mass and momentum
+    fn absorb(&mut self, other: &Particle) {
+        let mass = self.mass + other.mass;
+        let (weight, other_weight) = (self.mass / mass, other.mass / mass);
+
+        let blend = |a: u8, b: u8| (a as f32 * weight + b as f32 * other_weight).round() as u8;
+
+        self.position = self.position * weight + other.position * other_weight;
+        self.velocity = (self.momentum() + other.momentum()) / mass;
+        self.color = (
+            blend(self.color.0, other.color.0),
+            blend(self.color.1, other.color.1),
+            blend(self.color.2, other.color.2),
+        );
+        // mix temperatures by how much heat each side holds
+        let thermal_mass = self.thermal_mass() + other.thermal_mass();
+
+        if thermal_mass > 0.0 {
+            self.temperature = (self.temperature * self.thermal_mass()
+                + other.temperature * other.thermal_mass())
+                / thermal_mass;
+        }
+
+        self.heat_capacity = thermal_mass / mass;
+        self.charge += other.charge;
+
+        // keep the combined area
+        self.size = self.size.hypot(other.size);
+        self.mass = mass;
+        self.density = mass / self.size;
+    }
 }
 
 // this is the actual particle interaction physics
-fn handle_interaction(a: &mut Particle, b: &mut Particle, scale: f32) {
-    let mass_a = a.mass();
-    let pos_a = a.position();
 
-    let mass_b = b.mass();
-    let pos_b = b.position();
+/// `base + delta * step`, element-wise
+fn offset(base: &[Point], delta: &[Velocity], step: f32) -> Vec<Point> {
+    base.iter()
+        .zip(delta)
+        .map(|(base, delta)| *base + *delta * step)
+        .collect()
+}
+
+/// Time integration schemes, all driven by the same force evaluation
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Integrator {
+    /// Position and velocity both advance from the start-of-step state
+    ExplicitEuler,
+
+    /// Velocity first, then position with the new velocity
+    #[default]
+    SemiImplicitEuler,
+
+    /// Kick-drift-kick, with the acceleration re-evaluated at the new position
+    VelocityVerlet,
+
+    /// Drift-kick-drift, one force evaluation at the half step
+    Leapfrog,
+
+    /// Classic 4th-order Runge-Kutta (not symplectic, but very accurate for short runs)
+    #[serde(alias = "runge_kutta")]
+    Rk4,
+}
+
+/// How gravity between all particles is summed up
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ForceSolver {
+    /// Exact all-pairs sum, O(n²)
+    #[default]
+    Direct,
+
+    /// Quadtree approximation, O(n log n), accuracy controlled by the opening angle
+    BarnesHut,
+
+    /// Exact all-pairs sum as a plain n² loop over cached position and mass arrays, visiting each pair from
+    /// both ends so the inner loop is branch-free and left to the compiler to autovectorise
+    #[serde(alias = "soa", alias = "simd")]
+    Vectorised,
+}
+
+/// World size matching the canvas one-to-one
+pub fn default_resolution() -> (u32, u32) {
+    (FB_WIDTH as u32, FB_HEIGHT as u32)
+}
+
+fn viewport((width, height): (u32, u32)) -> Point {
+    Point::new(width as f32, height as f32)
+}
+
+pub fn default_world_size() -> Point {
+    Point::new(FB_WIDTH as f32, FB_HEIGHT as f32)
+}
+
+/// What happens at the edges of the world
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Boundary {
+    /// Edges wrap around, and forces and collisions act across them along the shortest path
+    #[default]
+    #[serde(alias = "wrap")]
+    Torus,
+
+    /// Edges are walls that particles bounce off
+    #[serde(alias = "reflecting")]
+    Reflect,
+
+    /// Nothing stops particles at the edges, but they are culled once they are entirely outside the world
+    Open,
+
+    /// Particles are removed as soon as they touch an edge
+    #[serde(alias = "absorbing")]
+    Absorb,
+}
+
+impl Boundary {
+    /// Shortest displacement from `a` to `b` in a world of the given size
+    pub fn separation(self, a: Point, b: Point, size: Point) -> Velocity {
+        let a_to_b = b - a;
+
+        match self {
+            Self::Torus => a_to_b - size * (a_to_b / size).round(),
+            _ => a_to_b,
+        }
+    }
+
+    /// Whether a particle is still part of the world
+    fn keeps(self, particle: &Particle, size: Point) -> bool {
+        let (min, max) = match self {
+            Self::Torus | Self::Reflect => return true,
+            Self::Open => (Point::splat(-particle.radius()), size + particle.radius()),
+            Self::Absorb => (Point::splat(particle.radius()), size - particle.radius()),
+        };
+
+        particle.position.cmpge(min).all() && particle.position.cmple(max).all()
+    }
+}
+
+/// What happens when two particles touch
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CollisionMode {
+    /// Particles pass through each other, and no collisions are reported
+    #[default]
+    Ignore,
 
-    let distance = pos_a.distance(pos_b);
+    /// Perfectly inelastic: the pair becomes one particle, conserving mass and momentum
+    Merge,
 
-    if distance < 1. {
-        println!("Collision! {} - {}", a.name(), b.name());
+    /// Perfectly elastic bounce, using each particle's radius
+    Bounce,
+
+    /// Merge, unless the impact energy is high enough to shatter the lighter particle
+    Fragment,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CollisionOutcome {
+    Merged,
+    Bounced,
+
+    /// The lighter particle broke into this many pieces
+    Fragmented(usize),
+}
+
+/// A collision resolved during a physics step
+#[derive(Clone, Debug)]
+pub struct Collision {
+    /// Heavier of the two particles
+    pub a: ParticleId,
+    pub b: ParticleId,
+
+    /// Names of `a` and `b` at the time, since `b` may not be around any more to ask
+    pub names: [String; 2],
+
+    /// Point of contact
+    pub position: Point,
+
+    /// Kinetic energy of the impact in the centre-of-mass frame
+    pub energy: f32,
+
+    pub outcome: CollisionOutcome,
+}
+
+/// How particle temperatures change over time
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThermalSettings {
+    /// Temperatures only change when this is on
+    pub enabled: bool,
+
+    /// How well surfaces emit and absorb radiation, from 0 to 1
+    pub emissivity: f32,
+
+    /// Temperature of empty space, which everything radiates towards (K)
+    pub ambient: f32,
+
+    /// Metres per world unit, used to work out surface areas and distances
+    pub length_scale: f32,
+
+    /// Share of the kinetic energy lost in a collision that ends up as heat
+    pub collision_heating: f32,
+}
+
+impl Default for ThermalSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            emissivity: 1.0,
+            ambient: 2.7,
+            length_scale: 0.01,
+            collision_heating: 1.0,
+        }
+    }
+}
+
+/// Warm both particles of a collision by the same amount, spending `energy` on it
+fn heat_pair(a: &mut Particle, b: &mut Particle, energy: f32) {
+    let thermal_mass = a.thermal_mass() + b.thermal_mass();
+
+    if thermal_mass > 0.0 && energy > 0.0 {
+        let warming = energy / thermal_mass;
+
+        a.temperature += warming;
+        b.temperature += warming;
     }
+}
+
+/// How many pieces a particle shatters into
+const FRAGMENTS: usize = 4;
+
+/// Mutable references to two distinct elements, `a < b`
+fn pair_mut<T>(items: &mut [T], a: usize, b: usize) -> (&mut T, &mut T) {
+    let (head, tail) = items.split_at_mut(b);
+
+    (&mut head[a], &mut tail[0])
+}
+
+/// Kinetic energy available to a collision, once the motion of the pair as a whole is removed
+fn impact_energy(a: &Particle, b: &Particle) -> f32 {
+    let reduced_mass = (a.mass * b.mass) / (a.mass + b.mass);
 
-    let a_to_b = (pos_b - pos_a) / distance;
+    0.5 * reduced_mass * (a.velocity - b.velocity).length_squared()
+}
+
+/// Slowest and fastest allowed time scales
+const TIME_SCALE_RANGE: (f32, f32) = (0.25, 16.0);
+
+/// Accumulates real time and hands it out in fixed-size simulation steps
+#[derive(Debug)]
+struct Clock {
+    /// Real time (s) not yet consumed by a step
+    accumulator: f32,
+
+    /// Steps per real second at 1x
+    step_rate: f32,
 
-    let distance2 = pos_a.distance_squared(pos_b);
+    /// Most steps taken in a single `advance`, anything beyond that is dropped
+    max_substeps: u32,
 
-    let force = (scale * G * mass_a * mass_b) / distance2;
+    time_scale: f32,
+    paused: bool,
 
-    let force_a = force / mass_a;
-    let _force_b_rel = force_a * (mass_a / mass_b);
+    /// Steps requested while paused
+    pending_steps: u32,
+}
+
+impl Clock {
+    fn new(step_rate: f32, max_substeps: u32) -> Self {
+        Self {
+            accumulator: 0.0,
+            step_rate,
+            max_substeps,
+            time_scale: 1.0,
+            paused: false,
+            pending_steps: 0,
+        }
+    }
 
-    let accel_a = a_to_b * force_a;
+    /// Number of steps due after `elapsed` real time has passed
+    fn tick(&mut self, elapsed: Duration) -> u32 {
+        if self.paused {
+            self.accumulator = 0.0;
+            return std::mem::take(&mut self.pending_steps);
+        }
 
-    *a.velocity_mut() += accel_a;
+        let interval = 1.0 / self.step_rate;
 
-    let vel_a = a.velocity();
+        self.accumulator += elapsed.as_secs_f32() * self.time_scale;
 
-    *a.position_mut() += vel_a;
+        let steps = (self.accumulator / interval).floor() as u32;
 
-    a.wrap_position();
+        if steps > self.max_substeps {
+            // falling behind, so drop the backlog instead of spiralling
+            self.accumulator = 0.0;
+            self.max_substeps
+        } else {
+            self.accumulator -= steps as f32 * interval;
+            steps
+        }
+    }
 }
 
 pub struct Simulator {
     environment: Vec<Particle>,
+    slots: Vec<Slot>,
+    free_slots: Vec<u32>,
     physics_scale: f32,
+    forces: Vec<Box<dyn Force>>,
+    constraints: Vec<Constraint>,
+    constraint_particles: Vec<[Option<ParticleId>; 2]>,
+    constraint_iterations: u32,
+    world_size: Point,
+    resolution: (u32, u32),
+    camera: Camera,
+    color_model: ColorModel,
+    blackbody_blend: f32,
+    integrator: Integrator,
+    solver: ForceSolver,
+    theta: f32,
+
+    /// Positions and masses laid out for the vectorised solver, refreshed in place every step
+    point_masses: PointMasses,
+    boundary: Boundary,
+    collision_mode: CollisionMode,
+    fragment_energy: f32,
+    thermal: ThermalSettings,
+    collisions: Vec<Collision>,
+    softening: f32,
+    quarantine: Vec<Particle>,
+    timestep: f32,
+    clock: Clock,
 }
 
 impl Simulator {
@@ -254,7 +730,147 @@ impl Simulator {
     pub fn new_with_scale(physics_scale: f32) -> Self {
         Self {
             environment: Vec::new(),
+            slots: Vec::new(),
+            free_slots: Vec::new(),
             physics_scale,
+            forces: default_forces()
+                .into_iter()
+                .map(ForceConfig::build)
+                .collect(),
+            constraints: Vec::new(),
+            constraint_particles: Vec::new(),
+            constraint_iterations: 8,
+            world_size: default_world_size(),
+            resolution: default_resolution(),
+            camera: Camera::fit(default_world_size(), viewport(default_resolution())),
+            color_model: ColorModel::default(),
+            blackbody_blend: 0.5,
+            integrator: Integrator::default(),
+            solver: ForceSolver::default(),
+            theta: 0.5,
+            point_masses: PointMasses::new(),
+            boundary: Boundary::default(),
+            collision_mode: CollisionMode::default(),
+            fragment_energy: 1e6,
+            thermal: ThermalSettings::default(),
+            collisions: Vec::new(),
+            softening: 0.0,
+            quarantine: Vec::new(),
+            timestep: 1.0,
+            clock: Clock::new(60.0, 64),
+        }
+    }
+
+    /// Build a simulator from a scene, resolving each particle's derived properties
+    pub fn from_scene(scene: Scene) -> Self {
+        let mut sim = Self::new();
+        sim.restore(scene);
+
+        sim
+    }
+
+    pub fn from_scene_file<P: AsRef<Path>>(path: P) -> Result<Self, SceneError> {
+        Scene::load(path).map(Self::from_scene)
+    }
+
+    /// Capture the current state exactly, so it can be saved and later restored.
+    ///
+    /// Post-processing isn't the simulator's to know; renderers fill it in with `Scene::set_render_config`
+    pub fn snapshot(&self) -> Scene {
+        Scene {
+            physics_scale: self.physics_scale,
+            forces: self
+                .forces
+                .iter()
+                .filter_map(|force| force.config())
+                .collect(),
+            constraints: self.constraints.clone(),
+            constraint_iterations: self.constraint_iterations,
+            world_size: self.world_size,
+            resolution: self.resolution,
+            camera: Some({
+                let mut camera = self.camera.clone();
+                camera.follow_name = self.followed().map(|particle| particle.name.clone());
+                camera
+            }),
+            color_model: self.color_model,
+            blackbody_blend: self.blackbody_blend,
+            integrator: self.integrator,
+            solver: self.solver,
+            theta: self.theta,
+            boundary: self.boundary,
+            collision_mode: self.collision_mode,
+            fragment_energy: self.fragment_energy,
+            thermal: self.thermal,
+            softening: self.softening,
+            timestep: self.timestep,
+            step_rate: self.clock.step_rate,
+            max_substeps: self.clock.max_substeps,
+            resolved: true,
+            particles: self.environment.clone(),
+            ..Scene::default()
+        }
+    }
+
+    /// Replace the current state with a scene.
+    ///
+    /// Snapshots are taken as-is; hand-written scenes have their particles' derived properties resolved
+    pub fn restore(&mut self, scene: Scene) {
+        self.physics_scale = scene.physics_scale;
+        self.set_forces(scene.forces);
+        self.world_size = scene.world_size;
+        self.resolution = scene.resolution;
+        self.camera = match scene.camera {
+            Some(mut camera) => {
+                camera.set_viewport(viewport(scene.resolution));
+                camera
+            }
+            None => Camera::fit(scene.world_size, viewport(scene.resolution)),
+        };
+        self.color_model = scene.color_model;
+        self.blackbody_blend = scene.blackbody_blend;
+        self.integrator = scene.integrator;
+        self.solver = scene.solver;
+        self.theta = scene.theta;
+        self.boundary = scene.boundary;
+        self.collision_mode = scene.collision_mode;
+        self.fragment_energy = scene.fragment_energy;
+        self.thermal = scene.thermal;
+        self.softening = scene.softening;
+        self.collisions.clear();
+        self.quarantine.clear();
+        self.timestep = scene.timestep;
+        self.clock = Clock::new(scene.step_rate, scene.max_substeps);
+        self.environment = scene.particles;
+
+        if !scene.resolved {
+            for particle in &mut self.environment {
+                particle.resolve_properties();
+            }
+        }
+
+        // handles from before don't carry over
+        self.slots.clear();
+        self.free_slots.clear();
+
+        for particle in &mut self.environment {
+            particle.id = None;
+        }
+
+        self.reindex();
+
+        self.camera.follow = self
+            .camera
+            .follow_name
+            .take()
+            .and_then(|name| self.find_by_name(&name));
+
+        self.constraints.clear();
+        self.constraint_particles.clear();
+        self.constraint_iterations = scene.constraint_iterations;
+
+        for constraint in scene.constraints {
+            self.add_constraint(constraint);
         }
     }
 
@@ -266,45 +882,1505 @@ impl Simulator {
         self.physics_scale
     }
 
-    pub fn add_particle(&mut self, particle: Particle) -> usize {
-        let idx = self.environment.len();
-        self.environment.push(particle);
+    /// Forces summed up every step, in order
+    pub fn forces(&self) -> &[Box<dyn Force>] {
+        &self.forces
+    }
 
-        idx
+    /// Replace all forces with built-in ones
+    pub fn set_forces(&mut self, forces: Vec<ForceConfig>) {
+        self.forces = forces.into_iter().map(ForceConfig::build).collect();
     }
 
-    pub fn remove_particle(&mut self, idx: usize) {
-        self.environment.remove(idx);
+    /// Add a force, built-in or custom. Custom forces aren't saved in snapshots
+    pub fn add_force<F: Force + 'static>(&mut self, force: F) {
+        self.forces.push(Box::new(force));
     }
 
-    pub fn step_physics(&mut self) {
-        let len = self.environment.len();
-        for idx in 0..len {
-            let (head, tail) = self.environment.split_at_mut(idx);
+    pub fn clear_forces(&mut self) {
+        self.forces.clear();
+    }
 
-            let (current, rest) = tail.split_first_mut().unwrap();
+    pub fn constraints(&self) -> &[Constraint] {
+        &self.constraints
+    }
 
-            for other in head {
-                handle_interaction(current, other, self.physics_scale);
-            }
+    /// Add a constraint between named particles, taking any missing length or anchor from where they are now
+    pub fn add_constraint(&mut self, mut constraint: Constraint) {
+        let environment = &self.environment;
+        let (boundary, world_size) = (self.boundary, self.world_size);
+
+        constraint.settle(
+            |name| {
+                environment
+                    .iter()
+                    .find(|particle| particle.name == name)
+                    .map(Particle::position)
+            },
+            |a, b| boundary.separation(a, b, world_size),
+        );
+
+        // bind to the particles now, so renames and later namesakes don't change what it holds
+        let particles = constraint
+            .particles()
+            .map(|name| name.and_then(|name| self.find_by_name(name)));
+
+        self.constraints.push(constraint);
+        self.constraint_particles.push(particles);
+    }
 
-            for other in rest {
-                handle_interaction(current, other, self.physics_scale);
-            }
-        }
+    /// Drop every constraint involving the particle
+    pub fn remove_constraints(&mut self, id: ParticleId) {
+        let (constraints, particles) = std::mem::take(&mut self.constraints)
+            .into_iter()
+            .zip(std::mem::take(&mut self.constraint_particles))
+            .filter(|(_, particles)| !particles.contains(&Some(id)))
+            .unzip();
+
+        self.constraints = constraints;
+        self.constraint_particles = particles;
     }
 
-    pub fn render(&self, canvas: &mut Canvas) {
-        for particle in &self.environment {
-            let coords = particle.normalize_position();
-            let color = particle.render_color();
-            canvas.particle(coords, particle.normalize_size(), color);
+    pub fn clear_constraints(&mut self) {
+        self.constraints.clear();
+        self.constraint_particles.clear();
+    }
+
+    /// Position-based passes made over rods and pins each step
+    pub fn set_constraint_iterations(&mut self, iterations: u32) {
+        self.constraint_iterations = iterations;
+    }
+
+    pub fn constraint_iterations(&self) -> u32 {
+        self.constraint_iterations
+    }
+
+    pub fn world_size(&self) -> Point {
+        self.world_size
+    }
+
+    pub fn set_world_size(&mut self, size: Point) {
+        self.world_size = size;
+    }
+
+    /// Canvas size in pixels
+    pub fn resolution(&self) -> (u32, u32) {
+        self.resolution
+    }
+
+    /// Change the canvas size, scaling the zoom so the same part of the world stays in view
+    pub fn set_resolution(&mut self, width: u32, height: u32) {
+        let new = viewport((width, height));
+
+        self.camera.zoom *= (new / self.camera.viewport()).min_element();
+        self.camera.set_viewport(new);
+        self.resolution = (width, height);
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    pub fn set_color_model(&mut self, model: ColorModel) {
+        self.color_model = model;
+    }
+
+    pub fn color_model(&self) -> ColorModel {
+        self.color_model
+    }
+
+    /// Share of blackbody color, from 0 to 1, when blending it with the authored color
+    pub fn set_blackbody_blend(&mut self, blend: f32) {
+        self.blackbody_blend = blend.clamp(0.0, 1.0);
+    }
+
+    pub fn blackbody_blend(&self) -> f32 {
+        self.blackbody_blend
+    }
+
+    /// Point the camera at the particle after the one it's following, in insertion order
+    pub fn follow_next(&mut self) {
+        let next = self
+            .camera
+            .follow
+            .and_then(|id| self.index_of(id))
+            .map_or(0, |idx| idx + 1);
+
+        match self.environment.get(next).and_then(|particle| particle.id) {
+            Some(id) => self.camera.follow(id),
+            None => self.camera.unfollow(),
         }
     }
-}
 
-impl Default for Simulator {
-    fn default() -> Self {
-        Self::new()
+    fn followed(&self) -> Option<&Particle> {
+        self.get(self.camera.follow?)
+    }
+
+    pub fn set_integrator(&mut self, integrator: Integrator) {
+        self.integrator = integrator;
+    }
+
+    pub fn integrator(&self) -> Integrator {
+        self.integrator
+    }
+
+    pub fn set_solver(&mut self, solver: ForceSolver) {
+        self.solver = solver;
+    }
+
+    pub fn solver(&self) -> ForceSolver {
+        self.solver
+    }
+
+    /// Barnes-Hut opening angle: smaller is more accurate, 0 is exact
+    pub fn set_theta(&mut self, theta: f32) {
+        self.theta = theta.max(0.0);
+    }
+
+    pub fn theta(&self) -> f32 {
+        self.theta
+    }
+
+    pub fn set_boundary(&mut self, boundary: Boundary) {
+        self.boundary = boundary;
+    }
+
+    pub fn boundary(&self) -> Boundary {
+        self.boundary
+    }
+
+    pub fn set_collision_mode(&mut self, mode: CollisionMode) {
+        self.collision_mode = mode;
+    }
+
+    pub fn collision_mode(&self) -> CollisionMode {
+        self.collision_mode
+    }
+
+    /// Impact energy above which `CollisionMode::Fragment` shatters particles
+    pub fn set_fragment_energy(&mut self, energy: f32) {
+        self.fragment_energy = energy;
+    }
+
+    pub fn fragment_energy(&self) -> f32 {
+        self.fragment_energy
+    }
+
+    pub fn thermal(&self) -> &ThermalSettings {
+        &self.thermal
+    }
+
+    pub fn set_thermal(&mut self, thermal: ThermalSettings) {
+        self.thermal = thermal;
+    }
+
+    /// Collisions since the last drain, oldest first
+    pub fn collisions(&self) -> &[Collision] {
+        &self.collisions
+    }
+
+    pub fn drain_collisions(&mut self) -> std::vec::Drain<'_, Collision> {
+        self.collisions.drain(..)
+    }
+
+    /// Plummer softening length; 0 is plain Newtonian gravity
+    pub fn set_softening(&mut self, softening: f32) {
+        self.softening = softening.max(0.0);
+    }
+
+    pub fn softening(&self) -> f32 {
+        self.softening
+    }
+
+    /// Particles pulled out of the simulation after their state stopped being finite
+    pub fn quarantined(&self) -> &[Particle] {
+        &self.quarantine
+    }
+
+    /// Simulation time covered by one step
+    pub fn set_timestep(&mut self, timestep: f32) {
+        self.timestep = timestep;
+    }
+
+    pub fn timestep(&self) -> f32 {
+        self.timestep
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.clock.time_scale
+    }
+
+    /// Clamped to 0.25x-16x
+    pub fn set_time_scale(&mut self, scale: f32) {
+        self.clock.time_scale = scale.clamp(TIME_SCALE_RANGE.0, TIME_SCALE_RANGE.1);
+    }
+
+    pub fn speed_up(&mut self) {
+        self.set_time_scale(self.clock.time_scale * 2.0);
+    }
+
+    pub fn slow_down(&mut self) {
+        self.set_time_scale(self.clock.time_scale / 2.0);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.clock.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.clock.paused = paused;
+        self.clock.pending_steps = 0;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.set_paused(!self.clock.paused);
+    }
+
+    /// Take exactly one step on the next `advance` (only while paused)
+    pub fn single_step(&mut self) {
+        if self.clock.paused {
+            self.clock.pending_steps += 1;
+        }
+    }
+
+    pub fn add_particle(&mut self, mut particle: Particle) -> ParticleId {
+        let id = self.allocate(self.environment.len());
+
+        particle.id = Some(id);
+        self.environment.push(particle);
+
+        id
+    }
+
+    /// Take a particle out of the simulation, if it's still there
+    pub fn remove_particle(&mut self, id: ParticleId) -> Option<Particle> {
+        let idx = self.index_of(id)?;
+        let particle = self.environment.remove(idx);
+
+        self.reindex();
+
+        Some(particle)
+    }
+
+    pub fn contains(&self, id: ParticleId) -> bool {
+        self.index_of(id).is_some()
+    }
+
+    pub fn get(&self, id: ParticleId) -> Option<&Particle> {
+        self.index_of(id).map(|idx| &self.environment[idx])
+    }
+
+    pub fn get_mut(&mut self, id: ParticleId) -> Option<&mut Particle> {
+        self.index_of(id).map(move |idx| &mut self.environment[idx])
+    }
+
+    /// Every particle with its handle, in insertion order
+    pub fn iter(&self) -> impl Iterator<Item = (ParticleId, &Particle)> {
+        self.environment
+            .iter()
+            .filter_map(|particle| Some((particle.id?, particle)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.environment.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.environment.is_empty()
+    }
+
+    /// First particle with the given name
+    pub fn find_by_name(&self, name: &str) -> Option<ParticleId> {
+        self.environment
+            .iter()
+            .find(|particle| particle.name == name)
+            .and_then(|particle| particle.id)
+    }
+
+    fn index_of(&self, id: ParticleId) -> Option<usize> {
+        self.slots
+            .get(id.slot as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.index)
+    }
+
+    /// A fresh handle for the particle at `index`, reusing a freed slot if there is one
+    fn allocate(&mut self, index: usize) -> ParticleId {
+        let slot = self.free_slots.pop().unwrap_or_else(|| {
+            self.slots.push(Slot::default());
+            (self.slots.len() - 1) as u32
+        });
+
+        self.slots[slot as usize].index = Some(index);
+
+        ParticleId {
+            slot,
+            generation: self.slots[slot as usize].generation,
+        }
+    }
+
+    /// Point every handle at where its particle is now, after particles were removed, merged or added.
+    ///
+    /// Slots whose particle is gone are freed, with their generation bumped so old handles stay dead
+    fn reindex(&mut self) {
+        let mut live = vec![false; self.slots.len()];
+
+        for idx in 0..self.environment.len() {
+            match self.environment[idx].id {
+                Some(id) => {
+                    self.slots[id.slot as usize].index = Some(idx);
+                    live[id.slot as usize] = true;
+                }
+
+                None => self.environment[idx].id = Some(self.allocate(idx)),
+            }
+        }
+
+        for (slot_idx, live) in live.into_iter().enumerate() {
+            let slot = &mut self.slots[slot_idx];
+
+            if !live && slot.index.is_some() {
+                slot.index = None;
+                slot.generation = slot.generation.wrapping_add(1);
+                self.free_slots.push(slot_idx as u32);
+            }
+        }
+    }
+
+    /// Run as many fixed steps as `elapsed` real time calls for, returning how many were taken
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        let steps = self.clock.tick(elapsed);
+
+        for _ in 0..steps {
+            self.step_physics(self.timestep);
+        }
+
+        steps
+    }
+
+    pub fn step_physics(&mut self, dt: f32) {
+        self.integrate(dt);
+
+        if self.thermal.enabled {
+            self.exchange_heat(dt);
+        }
+
+        if let Some(position) = self.followed().map(Particle::position) {
+            self.camera.center = position;
+        }
+    }
+
+    /// Constraints whose particles are all still around, by index
+    fn links(&self) -> Vec<Link> {
+        self.constraints
+            .iter()
+            .zip(&self.constraint_particles)
+            .filter_map(|(constraint, particles)| {
+                constraint.link(particles.map(|id| id.and_then(|id| self.index_of(id))))
+            })
+            .collect()
+    }
+
+    /// Acceleration on every particle, with all particles at `positions` moving at `velocities`
+    fn accelerations(
+        &mut self,
+        positions: &[Point],
+        velocities: &[Velocity],
+        links: &[Link],
+    ) -> Vec<Velocity> {
+        let vectorised = self.solver == ForceSolver::Vectorised;
+
+        if vectorised {
+            self.point_masses.set_positions(positions);
+        }
+
+        let system = System {
+            particles: &self.environment,
+            positions,
+            velocities,
+            boundary: self.boundary,
+            world_size: self.world_size,
+            physics_scale: self.physics_scale,
+            softening: self.softening,
+            solver: self.solver,
+            theta: self.theta,
+            point_masses: vectorised.then_some(&self.point_masses),
+        };
+
+        let mut accel = vec![Velocity::ZERO; positions.len()];
+
+        for force in &self.forces {
+            force.accumulate(&system, &mut accel);
+        }
+
+        for link in links {
+            if let Link::Spring {
+                a,
+                b,
+                stiffness,
+                rest_length,
+                damping,
+            } = *link
+            {
+                let force = spring_force(
+                    stiffness,
+                    rest_length,
+                    damping,
+                    system.separation(positions[a], positions[b]),
+                    velocities[b] - velocities[a],
+                );
+
+                accel[a] += force / self.environment[a].mass;
+                accel[b] -= force / self.environment[b].mass;
+            }
+        }
+
+        accel
+    }
+
+    fn integrate(&mut self, dt: f32) {
+        let x: Vec<Point> = self.environment.iter().map(Particle::position).collect();
+        let v: Vec<Velocity> = self.environment.iter().map(Particle::velocity).collect();
+        let links = self.links();
+
+        // masses and names only change between steps, positions are filled in for each force evaluation
+        if self.solver == ForceSolver::Vectorised {
+            self.point_masses.sync(&self.environment);
+        }
+
+        let (x, v) = match self.integrator {
+            Integrator::ExplicitEuler => {
+                let a = self.accelerations(&x, &v, &links);
+
+                (offset(&x, &v, dt), offset(&v, &a, dt))
+            }
+
+            Integrator::SemiImplicitEuler => {
+                let a = self.accelerations(&x, &v, &links);
+                let v = offset(&v, &a, dt);
+
+                (offset(&x, &v, dt), v)
+            }
+
+            Integrator::VelocityVerlet => {
+                let a = self.accelerations(&x, &v, &links);
+                let v_half = offset(&v, &a, dt * 0.5);
+                let x = offset(&x, &v_half, dt);
+
+                // velocity-dependent forces only see the half-step velocity here
+                let a = self.accelerations(&x, &v_half, &links);
+
+                (x, offset(&v_half, &a, dt * 0.5))
+            }
+
+            Integrator::Leapfrog => {
+                let x_half = offset(&x, &v, dt * 0.5);
+                let a = self.accelerations(&x_half, &v, &links);
+                let v = offset(&v, &a, dt);
+
+                (offset(&x_half, &v, dt * 0.5), v)
+            }
+
+            Integrator::Rk4 => {
+                let k1_x = v.clone();
+                let k1_v = self.accelerations(&x, &k1_x, &links);
+
+                let k2_x = offset(&v, &k1_v, dt * 0.5);
+                let k2_v = self.accelerations(&offset(&x, &k1_x, dt * 0.5), &k2_x, &links);
+
+                let k3_x = offset(&v, &k2_v, dt * 0.5);
+                let k3_v = self.accelerations(&offset(&x, &k2_x, dt * 0.5), &k3_x, &links);
+
+                let k4_x = offset(&v, &k3_v, dt);
+                let k4_v = self.accelerations(&offset(&x, &k3_x, dt), &k4_x, &links);
+
+                let weigh = |k1: &[Velocity], k2: &[Velocity], k3: &[Velocity], k4: &[Velocity]| {
+                    (0..k1.len())
+                        .map(|idx| (k1[idx] + 2.0 * k2[idx] + 2.0 * k3[idx] + k4[idx]) / 6.0)
+                        .collect::<Vec<Velocity>>()
+                };
+
+                (
+                    offset(&x, &weigh(&k1_x, &k2_x, &k3_x, &k4_x), dt),
+                    offset(&v, &weigh(&k1_v, &k2_v, &k3_v, &k4_v), dt),
+                )
+            }
+        };
+
+        for ((particle, position), velocity) in self.environment.iter_mut().zip(x).zip(v) {
+            *particle.position_mut() = position;
+            *particle.velocity_mut() = velocity;
+        }
+
+        self.solve_constraints(&links, dt);
+
+        // before the boundary, which would otherwise quietly drop blown-up particles as having left the world
+        self.quarantine_non_finite();
+        self.apply_boundary();
+        self.resolve_collisions();
+        self.reindex();
+    }
+
+    /// Pull rods back to length and pinned particles back into place, Gauss-Seidel style,
+    /// then fold the corrections into the velocities so the integrator doesn't undo them
+    fn solve_constraints(&mut self, links: &[Link], dt: f32) {
+        if links.iter().all(|link| matches!(link, Link::Spring { .. })) {
+            return;
+        }
+
+        let (boundary, world_size) = (self.boundary, self.world_size);
+        let environment = &mut self.environment;
+
+        // pinned particles can't be moved by rods
+        let mut inverse_mass: Vec<f32> = environment
+            .iter()
+            .map(|particle| particle.mass.recip())
+            .collect();
+
+        for link in links {
+            if let Link::Pin { particle, .. } = *link {
+                inverse_mass[particle] = 0.0;
+            }
+        }
+
+        let before: Vec<Point> = environment.iter().map(Particle::position).collect();
+
+        for _ in 0..self.constraint_iterations.max(1) {
+            for link in links {
+                match *link {
+                    Link::Rod { a, b, length } if a != b => {
+                        let a_to_b = boundary.separation(
+                            environment[a].position,
+                            environment[b].position,
+                            world_size,
+                        );
+                        let distance = a_to_b.length();
+                        let weight = inverse_mass[a] + inverse_mass[b];
+
+                        if distance == 0.0 || weight == 0.0 {
+                            continue;
+                        }
+
+                        let correction = a_to_b * ((distance - length) / (distance * weight));
+
+                        environment[a].position += correction * inverse_mass[a];
+                        environment[b].position -= correction * inverse_mass[b];
+                    }
+
+                    Link::Pin { particle, position } => environment[particle].position = position,
+
+                    _ => {}
+                }
+            }
+        }
+
+        if dt > 0.0 {
+            for (particle, before) in environment.iter_mut().zip(before) {
+                particle.velocity += (particle.position - before) / dt;
+            }
+        }
+
+        for link in links {
+            if let Link::Pin { particle, .. } = *link {
+                environment[particle].velocity = Velocity::ZERO;
+            }
+        }
+    }
+
+    /// Radiate heat away to the ambient background, and absorb it from every other particle.
+    ///
+    /// Particles are spheres for surface area and grey bodies for emission; a particle at
+    /// distance `d` catches the share of another's output falling on its cross-section
+    fn exchange_heat(&mut self, dt: f32) {
+        let ThermalSettings {
+            emissivity,
+            ambient,
+            length_scale,
+            ..
+        } = self.thermal;
+
+        let radius = |particle: &Particle| particle.radius() * length_scale;
+
+        let luminosity = |particle: &Particle| {
+            4.0 * π * radius(particle).powi(2) * emissivity * σ * particle.temperature.powi(4)
+        };
+
+        let background = σ * ambient.powi(4);
+
+        let power: Vec<f32> = self
+            .environment
+            .iter()
+            .enumerate()
+            .map(|(idx, particle)| {
+                let area = 4.0 * π * radius(particle).powi(2);
+                let cross_section = π * radius(particle).powi(2);
+
+                let absorbed: f32 = self
+                    .environment
+                    .iter()
+                    .enumerate()
+                    .filter(|&(other_idx, _)| other_idx != idx)
+                    .map(|(_, other)| {
+                        let distance = self
+                            .boundary
+                            .separation(particle.position, other.position, self.world_size)
+                            .length()
+                            .max(particle.radius() + other.radius())
+                            * length_scale;
+
+                        emissivity * luminosity(other) * cross_section
+                            / (4.0 * π * distance.powi(2))
+                    })
+                    .sum();
+
+                absorbed + emissivity * area * background - luminosity(particle)
+            })
+            .collect();
+
+        for (particle, power) in self.environment.iter_mut().zip(power) {
+            let thermal_mass = particle.thermal_mass();
+
+            if thermal_mass <= 0.0 {
+                continue;
+            }
+
+            // don't let a long step cool a particle past the background
+            let temperature = particle.temperature + power * dt / thermal_mass;
+            particle.temperature = temperature.max(particle.temperature.min(ambient));
+        }
+    }
+
+    fn apply_boundary(&mut self) {
+        let size = self.world_size;
+
+        match self.boundary {
+            Boundary::Torus => {
+                for particle in &mut self.environment {
+                    particle.wrap_position(size);
+                }
+            }
+
+            Boundary::Reflect => {
+                for particle in &mut self.environment {
+                    for axis in 0..2 {
+                        let (position, velocity) =
+                            (&mut particle.position[axis], &mut particle.velocity[axis]);
+
+                        if *position < 0.0 {
+                            *position = -*position;
+                            *velocity = velocity.abs();
+                        } else if *position > size[axis] {
+                            *position = 2.0 * size[axis] - *position;
+                            *velocity = -velocity.abs();
+                        }
+
+                        // only overshoots of more than a whole world width get here
+                        *position = position.clamp(0.0, size[axis]);
+                    }
+                }
+            }
+
+            boundary @ (Boundary::Open | Boundary::Absorb) => {
+                self.environment.retain(|particle| {
+                    let keep = boundary.keeps(particle, size);
+
+                    if !keep {
+                        debug!("particle '{}' left the world", particle.name);
+                    }
+
+                    keep
+                });
+            }
+        }
+    }
+
+    fn quarantine_non_finite(&mut self) {
+        if self
+            .environment
+            .iter()
+            .all(|particle| particle.position.is_finite() && particle.velocity.is_finite())
+        {
+            return;
+        }
+
+        let (good, bad) = std::mem::take(&mut self.environment)
+            .into_iter()
+            .partition(|particle| particle.position.is_finite() && particle.velocity.is_finite());
+
+        self.environment = good;
+
+        for particle in bad {
+            warn!(
+                "quarantining particle '{}': position {}, velocity {}",
+                particle.name, particle.position, particle.velocity
+            );
+
+            self.quarantine.push(particle);
+        }
+    }
+
+    /// Overlapping pairs `(a, b)` with `a < b`, found by sweeping along x
+    fn contacts(&self) -> Vec<(usize, usize)> {
+        let width = self.world_size.x;
+
+        // x extents as `(left, right, index)`
+        let mut spans = Vec::with_capacity(self.environment.len());
+
+        for (idx, particle) in self.environment.iter().enumerate() {
+            let (left, right) = (
+                particle.position.x - particle.radius(),
+                particle.position.x + particle.radius(),
+            );
+
+            spans.push((left, right, idx));
+
+            // on a torus, anything hanging over an edge also shows up a world away, next to the other edge
+            if self.boundary == Boundary::Torus {
+                if left < 0.0 {
+                    spans.push((left + width, right + width, idx));
+                }
+
+                if right > width {
+                    spans.push((left - width, right - width, idx));
+                }
+            }
+        }
+
+        spans.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut pairs = Vec::new();
+
+        for (start, &(_, right, a)) in spans.iter().enumerate() {
+            let particle = &self.environment[a];
+
+            for &(left, _, b) in &spans[start + 1..] {
+                if left > right {
+                    break;
+                }
+
+                if a != b && particle.overlaps(&self.environment[b], self.boundary, self.world_size)
+                {
+                    pairs.push((a.min(b), a.max(b)));
+                }
+            }
+        }
+
+        // a pair straddling the seam is found from both sides
+        if self.boundary == Boundary::Torus {
+            pairs.sort_unstable();
+            pairs.dedup();
+        }
+
+        pairs
+    }
+
+    fn resolve_collisions(&mut self) {
+        if self.collision_mode == CollisionMode::Ignore {
+            return;
+        }
+
+        let mut removed = vec![false; self.environment.len()];
+        let mut debris = Vec::new();
+
+        for (a, b) in self.contacts() {
+            if removed[a] || removed[b] {
+                continue;
+            }
+
+            let (heavy, light) = pair_mut(&mut self.environment, a, b);
+
+            // earlier merges this step may have moved things apart
+            if !heavy.overlaps(light, self.boundary, self.world_size) {
+                continue;
+            }
+
+            // keep the heavier one in the lower slot, which is the one that survives merges
+            if light.mass > heavy.mass {
+                std::mem::swap(heavy, light);
+            }
+
+            // work next to the heavy one, on whichever side of the seam that is; positions are wrapped afterwards
+            light.position = heavy.position
+                + self
+                    .boundary
+                    .separation(heavy.position, light.position, self.world_size);
+
+            let energy = impact_energy(heavy, light);
+            let normal = (light.position - heavy.position)
+                .try_normalize()
+                .unwrap_or(Point::X);
+            let position = heavy.position + normal * heavy.radius();
+
+            let event = |outcome| Collision {
+                a: heavy.id.expect("particles in the simulator have handles"),
+                b: light.id.expect("particles in the simulator have handles"),
+                names: [heavy.name.clone(), light.name.clone()],
+                position,
+                energy,
+                outcome,
+            };
+
+            let heating = if self.thermal.enabled {
+                self.thermal.collision_heating
+            } else {
+                0.0
+            };
+
+            let shatter = self.collision_mode == CollisionMode::Fragment
+                && energy > self.fragment_energy
+                && light.size / (FRAGMENTS as f32).sqrt() >= 1.0;
+
+            let collision = if self.collision_mode == CollisionMode::Bounce {
+                let collision = event(CollisionOutcome::Bounced);
+                let total = heavy.mass + light.mass;
+
+                let closing = (heavy.velocity - light.velocity).dot(normal);
+
+                if closing > 0.0 {
+                    let impulse = normal * (2.0 * closing * heavy.mass * light.mass / total);
+
+                    heavy.velocity -= impulse / heavy.mass;
+                    light.velocity += impulse / light.mass;
+                }
+
+                // push apart so they don't stay tangled
+                let overlap =
+                    heavy.radius() + light.radius() - heavy.position.distance(light.position);
+
+                heavy.position -= normal * (overlap * light.mass / total);
+                light.position += normal * (overlap * heavy.mass / total);
+
+                collision
+            } else if shatter {
+                let collision = event(CollisionOutcome::Fragmented(FRAGMENTS));
+                let total = heavy.mass + light.mass;
+
+                let velocity = (heavy.momentum() + light.momentum()) / total;
+
+                // whatever wasn't spent breaking it up flings the pieces apart
+                let speed = (2.0 * (energy - self.fragment_energy) / light.mass).sqrt();
+
+                // the energy spent breaking it up is lost as heat
+                heat_pair(heavy, light, self.fragment_energy * heating);
+
+                let mass = light.mass / FRAGMENTS as f32;
+                let size = light.size / (FRAGMENTS as f32).sqrt();
+
+                heavy.velocity = velocity;
+
+                debris.extend((0..FRAGMENTS).map(|piece| {
+                    let angle = 2.0 * π * piece as f32 / FRAGMENTS as f32;
+                    let direction = glam::Mat2::from_angle(angle) * normal;
+
+                    Particle {
+                        id: None,
+                        name: format!("{} #{}", light.name, piece + 1),
+                        position: light.position + direction * light.radius(),
+                        velocity: velocity + direction * speed,
+                        size,
+                        mass,
+                        charge: light.charge / FRAGMENTS as f32,
+                        density: mass / size,
+                        ..light.clone()
+                    }
+                }));
+
+                removed[b] = true;
+
+                collision
+            } else {
+                let collision = event(CollisionOutcome::Merged);
+
+                // all of the motion the pair doesn't share is lost when they stick together
+                heat_pair(heavy, light, energy * heating);
+
+                heavy.absorb(light);
+                removed[b] = true;
+
+                collision
+            };
+
+            self.collisions.push(collision);
+        }
+
+        let mut idx = 0;
+        self.environment.retain(|_| {
+            idx += 1;
+            !removed[idx - 1]
+        });
+
+        self.environment.extend(debris);
+
+        if self.boundary == Boundary::Torus {
+            for particle in &mut self.environment {
+                particle.wrap_position(self.world_size);
+            }
+        }
+    }
+
+    pub fn render(&self, canvas: &mut Canvas) {
+        let center = self
+            .followed()
+            .map_or(self.camera.center, Particle::position);
+
+        let link_color = RgbaF16::rgb(90, 90, 90);
+
+        for link in self.links() {
+            let (a, b) = match link {
+                Link::Spring { a, b, .. } | Link::Rod { a, b, .. } => (a, b),
+                Link::Pin { .. } => continue,
+            };
+
+            let a = center
+                + self
+                    .boundary
+                    .separation(center, self.environment[a].position, self.world_size);
+            let b = a + self
+                .boundary
+                .separation(a, self.environment[b].position, self.world_size);
+
+            if let (Some(start), Some(end)) = (self.camera.project(a), self.camera.project(b)) {
+                canvas.line(start, end, link_color);
+            }
+        }
+
+        for particle in &self.environment {
+            // on the torus, draw each particle at its closest image to the middle of the view
+            let position = center
+                + self
+                    .boundary
+                    .separation(center, particle.position, self.world_size);
+
+            if let Some(coords) = self.camera.project(position) {
+                let color = particle.render_color(self.color_model, self.blackbody_blend);
+                canvas.particle(coords, self.camera.project_size(particle.size), color);
+            }
+        }
+    }
+}
+
+impl Default for Simulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::force::{Coulomb, UniformField, G};
+
+    fn body(x: f32, y: f32) -> Particle {
+        Particle::new(
+            Point::new(x, y),
+            Velocity::ZERO,
+            default_color(),
+            0.0,
+            1.0,
+            1e9,
+            0.0,
+        )
+    }
+
+    fn pair(distance: f32, softening: f32) -> Simulator {
+        let mut sim = Simulator::new();
+        sim.set_softening(softening);
+
+        sim.add_particle(body(100.0, 100.0));
+        sim.add_particle(body(100.0 + distance, 100.0));
+
+        sim
+    }
+
+    /// Kinetic plus gravitational potential energy
+    fn energy(sim: &Simulator) -> f32 {
+        let particles = &sim.environment;
+
+        let kinetic: f32 = particles
+            .iter()
+            .map(|particle| 0.5 * particle.mass * particle.velocity.length_squared())
+            .sum();
+
+        let potential: f32 = (0..particles.len())
+            .flat_map(|a| (a + 1..particles.len()).map(move |b| (a, b)))
+            .map(|(a, b)| {
+                let (a, b) = (&particles[a], &particles[b]);
+
+                -G * sim.physics_scale * a.mass * b.mass / a.position.distance(b.position)
+            })
+            .sum();
+
+        kinetic + potential
+    }
+
+    /// A light body on a circular orbit around a heavy one, with no net momentum
+    fn orbit(integrator: Integrator) -> Simulator {
+        let (heavy, light, radius) = (1e9, 1e3, 20.0);
+        let speed = (G * heavy / radius).sqrt();
+
+        let mut sim = Simulator::new();
+        sim.set_boundary(Boundary::Open);
+        sim.set_integrator(integrator);
+
+        let mut sun = body(100.0, 100.0);
+        sun.velocity = Velocity::new(0.0, -speed * light / heavy);
+
+        let mut planet = body(100.0 + radius, 100.0);
+        planet.mass = light;
+        planet.velocity = Velocity::new(0.0, speed);
+
+        sim.add_particle(sun);
+        sim.add_particle(planet);
+
+        sim
+    }
+
+    fn all_finite(sim: &Simulator) -> bool {
+        sim.environment
+            .iter()
+            .all(|particle| particle.position.is_finite() && particle.velocity.is_finite())
+    }
+
+    #[test]
+    fn clock_steps_at_a_fixed_rate() {
+        let mut clock = Clock::new(10.0, 64);
+
+        assert_eq!(clock.tick(Duration::from_millis(250)), 2);
+
+        // the leftover 50ms carries over into the next frame
+        assert_eq!(clock.tick(Duration::from_millis(60)), 1);
+        assert_eq!(clock.tick(Duration::from_millis(30)), 0);
+
+        clock.time_scale = 2.0;
+        assert_eq!(clock.tick(Duration::from_millis(100)), 2);
+    }
+
+    #[test]
+    fn clock_drops_the_backlog_after_a_stall() {
+        let mut clock = Clock::new(60.0, 8);
+
+        assert_eq!(clock.tick(Duration::from_secs(10)), 8);
+
+        // nothing owed from the stall afterwards
+        assert_eq!(clock.tick(Duration::from_millis(10)), 0);
+    }
+
+    #[test]
+    fn symplectic_and_rk4_orbits_keep_their_energy() {
+        for integrator in [Integrator::VelocityVerlet, Integrator::Rk4] {
+            let mut sim = orbit(integrator);
+            let start = energy(&sim);
+
+            // a little over one orbit
+            for _ in 0..2500 {
+                sim.step_physics(1.0);
+            }
+
+            let drift = ((energy(&sim) - start) / start).abs();
+            assert!(drift < 1e-3, "{integrator:?} drifted by {drift}");
+        }
+    }
+
+    #[test]
+    fn vectorised_solver_tracks_direct() {
+        let mut direct = orbit(Integrator::Rk4);
+        let mut vectorised = orbit(Integrator::Rk4);
+        vectorised.set_solver(ForceSolver::Vectorised);
+
+        for _ in 0..500 {
+            direct.step_physics(1.0);
+            vectorised.step_physics(1.0);
+        }
+
+        for ((_, exact), (_, fast)) in direct.iter().zip(vectorised.iter()) {
+            assert!(exact.position().distance(fast.position()) < 1e-3);
+        }
+    }
+
+    #[test]
+    fn accelerations_ignore_particle_order() {
+        let bodies = [body(100.0, 100.0), body(130.0, 90.0), body(80.0, 140.0)];
+
+        let accelerations = |bodies: &[Particle]| {
+            let mut sim = Simulator::new();
+
+            for body in bodies {
+                sim.add_particle(body.clone());
+            }
+
+            let x: Vec<Point> = bodies.iter().map(Particle::position).collect();
+            let v = vec![Velocity::ZERO; bodies.len()];
+
+            sim.accelerations(&x, &v, &[])
+        };
+
+        let forward = accelerations(&bodies);
+
+        let mut reversed = bodies.to_vec();
+        reversed.reverse();
+        let mut backward = accelerations(&reversed);
+        backward.reverse();
+
+        for (a, b) in forward.iter().zip(&backward) {
+            assert!((*a - *b).length() <= a.length() * 1e-5);
+        }
+    }
+
+    /// Two overlapping particles of different mass heading into each other
+    fn head_on(mode: CollisionMode) -> Simulator {
+        let mut sim = Simulator::new();
+        sim.set_collision_mode(mode);
+
+        let mut heavy = body(100.0, 100.0);
+        heavy.mass = 3e9;
+        heavy.velocity = Velocity::new(0.5, 0.1);
+
+        let mut light = body(100.5, 100.0);
+        light.velocity = Velocity::new(-1.0, 0.0);
+
+        sim.add_particle(heavy);
+        sim.add_particle(light);
+
+        sim
+    }
+
+    fn momentum(sim: &Simulator) -> Velocity {
+        sim.environment
+            .iter()
+            .fold(Velocity::ZERO, |total, particle| {
+                total + particle.momentum()
+            })
+    }
+
+    fn kinetic_energy(sim: &Simulator) -> f32 {
+        sim.environment
+            .iter()
+            .map(|particle| 0.5 * particle.mass * particle.velocity.length_squared())
+            .sum()
+    }
+
+    fn total_mass(sim: &Simulator) -> f32 {
+        sim.environment.iter().map(Particle::mass).sum()
+    }
+
+    #[test]
+    fn elastic_bounce_conserves_momentum_and_energy() {
+        let mut sim = head_on(CollisionMode::Bounce);
+        let (momentum_before, energy_before) = (momentum(&sim), kinetic_energy(&sim));
+
+        sim.resolve_collisions();
+
+        assert_eq!(sim.collisions().len(), 1);
+        assert_eq!(sim.collisions()[0].outcome, CollisionOutcome::Bounced);
+
+        // the light one came back the way it came
+        assert!(sim.environment[1].velocity.x > 0.0);
+
+        assert!((momentum(&sim) - momentum_before).length() <= momentum_before.length() * 1e-5);
+        assert!((kinetic_energy(&sim) - energy_before).abs() <= energy_before * 1e-5);
+    }
+
+    #[test]
+    fn merging_conserves_momentum_and_mass() {
+        let mut sim = head_on(CollisionMode::Merge);
+        let (momentum_before, mass_before) = (momentum(&sim), total_mass(&sim));
+
+        sim.resolve_collisions();
+
+        assert_eq!(sim.environment.len(), 1);
+        assert_eq!(sim.collisions()[0].outcome, CollisionOutcome::Merged);
+
+        assert!((momentum(&sim) - momentum_before).length() <= momentum_before.length() * 1e-5);
+        assert!((total_mass(&sim) - mass_before).abs() <= mass_before * 1e-6);
+    }
+
+    #[test]
+    fn collisions_reach_across_the_seam() {
+        let mut sim = head_on(CollisionMode::Merge);
+        sim.set_boundary(Boundary::Torus);
+
+        let width = sim.world_size.x;
+        let mass_before = total_mass(&sim);
+
+        // the same pair, now touching over the left/right edge
+        sim.environment[0].position = Point::new(width - 0.2, 100.0);
+        sim.environment[1].position = Point::new(0.3, 100.0);
+
+        sim.resolve_collisions();
+
+        assert_eq!(sim.collisions().len(), 1);
+        assert_eq!(sim.environment.len(), 1);
+        assert!((total_mass(&sim) - mass_before).abs() <= mass_before * 1e-6);
+
+        // merged just short of the seam, not halfway across the world
+        let merged = sim.environment[0].position;
+        assert!(
+            merged.x > width - 0.2 && merged.x < width,
+            "merged at {merged}"
+        );
+    }
+
+    #[test]
+    fn rods_hold_pendulum_length() {
+        let mut sim = Simulator::new();
+        sim.set_forces(vec![ForceConfig::UniformField(UniformField {
+            gravity: Velocity::new(0.0, 0.1),
+            ..UniformField::default()
+        })]);
+
+        sim.add_particle(body(100.0, 100.0).with_name("pivot"));
+        sim.add_particle(body(110.0, 100.0).with_name("bob"));
+        sim.add_constraint(Constraint::pin("pivot"));
+        sim.add_constraint(Constraint::rod("pivot", "bob"));
+
+        for _ in 0..200 {
+            sim.step_physics(1.0);
+
+            let (pivot, bob) = (&sim.environment[0], &sim.environment[1]);
+
+            assert_eq!(pivot.position, Point::new(100.0, 100.0));
+            assert!((pivot.position.distance(bob.position) - 10.0).abs() < 1e-3);
+        }
+
+        // it swung down
+        assert!(sim.environment[1].position.y > 100.0);
+    }
+
+    #[test]
+    fn ids_survive_removal_and_merges() {
+        let mut sim = Simulator::new();
+
+        let first = sim.add_particle(body(10.0, 10.0).with_name("a"));
+        let second = sim.add_particle(body(50.0, 50.0).with_name("b"));
+        let third = sim.add_particle(body(50.5, 50.0).with_name("c"));
+
+        assert_eq!(
+            sim.remove_particle(first).map(|particle| particle.name),
+            Some("a".into())
+        );
+        assert!(sim.get(first).is_none());
+        assert_eq!(sim.get(second).map(Particle::name), Some("b"));
+        assert_eq!(sim.find_by_name("c"), Some(third));
+
+        // the freed slot comes back with a new generation
+        let fourth = sim.add_particle(body(200.0, 200.0).with_name("d"));
+        assert_ne!(first, fourth);
+        assert!(sim.get(first).is_none());
+
+        sim.set_collision_mode(CollisionMode::Merge);
+        sim.step_physics(0.01);
+
+        // b and c overlap; only one of them survives the merge
+        assert_eq!(sim.len(), 2);
+        assert!(sim.contains(second) != sim.contains(third));
+        assert_eq!(sim.get(fourth).map(Particle::name), Some("d"));
+        assert_eq!(sim.iter().count(), 2);
+    }
+
+    #[test]
+    fn camera_and_collisions_use_handles() {
+        let mut sim = Simulator::new();
+        sim.set_boundary(Boundary::Open);
+        sim.set_collision_mode(CollisionMode::Merge);
+
+        // same name, so only the handle tells them apart
+        let first = sim.add_particle(body(50.0, 50.0).with_name("twin"));
+        let second = sim.add_particle(body(150.0, 150.0).with_name("twin"));
+        let third = sim.add_particle(body(150.5, 150.0));
+
+        sim.camera_mut().follow(second);
+        sim.step_physics(1.0);
+
+        assert_eq!(sim.camera().center, sim.get(second).unwrap().position());
+
+        let collision = &sim.collisions()[0];
+        assert_eq!([collision.a, collision.b], [second, third]);
+        assert!(sim.contains(first));
+
+        // the followed particle is written out by name, and found again on load
+        let loaded = Simulator::from_scene(sim.snapshot());
+        let followed = loaded.camera().follow.and_then(|id| loaded.get(id));
+
+        assert_eq!(followed.map(Particle::name), Some("twin"));
+
+        sim.add_constraint(Constraint::rod("twin", ""));
+        sim.remove_constraints(first);
+        assert!(sim.constraints().is_empty());
+    }
+
+    #[test]
+    fn blackbody_cools_towards_red() {
+        let hot = blackbody(10_000.0);
+        let warm = blackbody(3_000.0);
+        let cold = blackbody(default_temp());
+
+        assert!(hot[2] > hot[0]);
+        assert!(warm[0] > warm[2]);
+        assert_eq!(cold, [1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn hot_body_cools_to_ambient() {
+        let mut sim = Simulator::new();
+        sim.set_thermal(ThermalSettings {
+            enabled: true,
+            ambient: 100.0,
+            ..ThermalSettings::default()
+        });
+
+        // light enough to cool noticeably
+        let mut particle = body(50.0, 50.0).with_heat_capacity(1e-3);
+        particle.temperature = 6000.0;
+        sim.add_particle(particle);
+
+        let mut last = 6000.0;
+
+        for _ in 0..1000 {
+            sim.step_physics(1000.0);
+
+            // cooling all the way, without ever undershooting the surroundings
+            let temperature = sim.environment[0].temperature();
+            assert!(temperature <= last && temperature >= 100.0);
+            last = temperature;
+        }
+
+        assert!((last - 100.0).abs() < 0.5, "settled at {last}");
+    }
+
+    #[test]
+    fn merging_releases_heat() {
+        let mut sim = pair(0.5, 0.0);
+        sim.set_collision_mode(CollisionMode::Merge);
+        sim.set_thermal(ThermalSettings {
+            enabled: true,
+            length_scale: 0.0,
+            ..ThermalSettings::default()
+        });
+
+        sim.environment[1].velocity = Velocity::new(-10.0, 0.0);
+        sim.step_physics(0.01);
+
+        assert_eq!(sim.environment.len(), 1);
+        assert!(sim.environment[0].temperature() > default_temp());
+    }
+
+    #[test]
+    fn like_charges_repel() {
+        for (charges, closer) in [((1.0, 1.0), false), ((1.0, -1.0), true)] {
+            let mut sim = pair(10.0, 0.0);
+            sim.set_forces(vec![ForceConfig::Coulomb(Coulomb::default())]);
+
+            sim.environment[0].charge = charges.0;
+            sim.environment[1].charge = charges.1;
+            sim.step_physics(1.0);
+
+            let distance = sim.environment[0]
+                .position
+                .distance(sim.environment[1].position);
+
+            assert_eq!(distance < 10.0, closer);
+        }
+    }
+
+    #[test]
+    fn coincident_pair_stays_finite() {
+        for softening in [0.0, 0.1] {
+            let mut sim = pair(0.0, softening);
+
+            for _ in 0..10 {
+                sim.step_physics(1.0);
+            }
+
+            assert_eq!(sim.environment.len(), 2);
+            assert!(all_finite(&sim));
+        }
+    }
+
+    #[test]
+    fn near_coincident_pair_stays_finite() {
+        for integrator in [Integrator::SemiImplicitEuler, Integrator::Rk4] {
+            let mut sim = pair(1e-4, 0.1);
+            sim.set_integrator(integrator);
+
+            for _ in 0..10 {
+                sim.step_physics(1.0);
+            }
+
+            assert!(all_finite(&sim));
+        }
+    }
+
+    #[test]
+    fn snapshots_survive_toml() {
+        let mut sim = Simulator::from_scene_file("scenes/default.toml").unwrap();
+
+        let reload = |sim: &Simulator| {
+            let source = sim.snapshot().to_toml().unwrap();
+            Simulator::from_scene(source.parse().unwrap())
+        };
+
+        let loaded = reload(&sim);
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(loaded.forces().len(), 1);
+        assert!(loaded.constraints().is_empty());
+
+        for ((_, before), (_, after)) in sim.iter().zip(loaded.iter()) {
+            assert_eq!(before.name(), after.name());
+            assert_eq!(before.position(), after.position());
+            assert_eq!(before.velocity(), after.velocity());
+        }
+
+        // cleared lists have to stay cleared rather than coming back as the defaults
+        sim.clear_forces();
+
+        let loaded = reload(&sim);
+        assert!(loaded.forces().is_empty());
+
+        let mut scene = sim.snapshot();
+        scene.post.clear();
+
+        let scene: Scene = scene.to_toml().unwrap().parse().unwrap();
+        assert!(scene.post.is_empty());
+
+        assert!(reload(&Simulator::new()).is_empty());
+    }
+
+    #[test]
+    fn open_boundary_culls_particles_that_leave_the_world() {
+        let mut sim = Simulator::new();
+        sim.set_boundary(Boundary::Open);
+
+        let width = sim.world_size.x;
+
+        // one still overlapping the right edge, one just clear of it
+        let edge = sim.add_particle(body(width + 0.4, 100.0));
+        let gone = sim.add_particle(body(width + 0.6, 100.0));
+
+        sim.apply_boundary();
+        sim.reindex();
+
+        assert!(sim.contains(edge));
+        assert!(!sim.contains(gone));
+    }
+
+    #[test]
+    fn non_finite_particles_are_quarantined() {
+        for boundary in [Boundary::Torus, Boundary::Open, Boundary::Absorb] {
+            let mut sim = pair(10.0, 0.0);
+            sim.set_boundary(boundary);
+            sim.add_particle(body(f32::NAN, 0.0).with_name("bad"));
+
+            sim.step_physics(1.0);
+
+            assert_eq!(sim.environment.len(), 2);
+            assert!(all_finite(&sim));
+            assert_eq!(sim.quarantined().len(), 1, "{boundary:?}");
+            assert_eq!(sim.quarantined()[0].name(), "bad");
+        }
     }
 }
@@ -0,0 +1,137 @@
+// MIT License
+
+// Copyright (c) 2022 AnonmousDapper
+
+use crate::simulation::{Particle, Point, Velocity};
+
+/// Adding and then subtracting 1.5 × 2²³ rounds an `f32` to the nearest integer without a call to `round`,
+/// which keeps the wrapping loop vectorised. Only exact below 2²², far beyond any sensible world
+const ROUNDING: f32 = 12_582_912.0;
+
+/// Positions and masses of the particles, one array per coordinate, cached for the vectorised gravity solver.
+///
+/// The simulator keeps one across steps, refreshing the masses once a step and the positions for every
+/// force evaluation, so the kernel streams through contiguous `f32`s instead of whole `Particle`s
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PointMasses {
+    x: Vec<f32>,
+    y: Vec<f32>,
+    mass: Vec<f32>,
+}
+
+impl PointMasses {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_particles(particles: &[Particle]) -> Self {
+        let mut cache = Self::new();
+        cache.sync(particles);
+
+        cache
+    }
+
+    /// Bring the cache up to date with `particles`, reusing its storage
+    pub fn sync(&mut self, particles: &[Particle]) {
+        let len = particles.len();
+
+        for array in [&mut self.x, &mut self.y, &mut self.mass] {
+            array.resize(len, 0.0);
+        }
+
+        for (idx, particle) in particles.iter().enumerate() {
+            let position = particle.position();
+
+            self.x[idx] = position.x;
+            self.y[idx] = position.y;
+            self.mass[idx] = particle.mass();
+        }
+    }
+
+    /// Overwrite the positions, as integrators do partway through a step
+    pub fn set_positions(&mut self, positions: &[Point]) {
+        for ((x, y), position) in self.x.iter_mut().zip(&mut self.y).zip(positions) {
+            *x = position.x;
+            *y = position.y;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.x.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.x.is_empty()
+    }
+
+    /// Add each particle's gravitational acceleration to `accel`. See `gravity`
+    pub fn gravity(
+        &self,
+        strength: f32,
+        softening: f32,
+        wrap: Option<Point>,
+        accel: &mut [Velocity],
+    ) {
+        gravity(
+            &self.x, &self.y, &self.mass, strength, softening, wrap, accel,
+        );
+    }
+}
+
+/// Pairwise gravity over positions and masses in separate arrays, adding each particle's acceleration to `accel`.
+///
+/// `strength` is G times the physics scale, `softening` the Plummer length, and `wrap` the world size when
+/// separations are taken across the edges of a torus. This is a plain n² loop with every pair visited from
+/// both ends: twice the arithmetic of the direct sum, but with a branch-free inner loop over contiguous
+/// arrays that the compiler autovectorises
+pub fn gravity(
+    x: &[f32],
+    y: &[f32],
+    mass: &[f32],
+    strength: f32,
+    softening: f32,
+    wrap: Option<Point>,
+    accel: &mut [Velocity],
+) {
+    let len = x.len().min(y.len()).min(mass.len()).min(accel.len());
+    let (x, y, mass) = (&x[..len], &y[..len], &mass[..len]);
+
+    let softening2 = softening * softening;
+
+    // a zero size and its zero reciprocal leave separations alone, so one loop serves both cases
+    let size = wrap.unwrap_or(Point::ZERO);
+    let inv_size = wrap.map_or(Point::ZERO, Point::recip);
+
+    let pull = |xi: f32, yi: f32, xj: f32, yj: f32, mj: f32| {
+        let mut dx = xj - xi;
+        let mut dy = yj - yi;
+
+        dx -= size.x * ((dx * inv_size.x + ROUNDING) - ROUNDING);
+        dy -= size.y * ((dy * inv_size.y + ROUNDING) - ROUNDING);
+
+        let distance2 = dx * dx + dy * dy + softening2;
+        let falloff = (distance2 * distance2.sqrt()).recip();
+
+        // a particle against itself, or a coincident one with no softening, has no direction to pull in
+        let falloff = if falloff.is_finite() {
+            falloff * mj
+        } else {
+            0.0
+        };
+
+        (dx * falloff, dy * falloff)
+    };
+
+    // sources outside, targets inside: the inner loop is an element-wise update with no reduction,
+    // so it can be vectorised without reordering any float sums
+    for ((&xj, &yj), &mj) in x.iter().zip(y).zip(mass) {
+        let mj = mj * strength;
+
+        for ((accel, &xi), &yi) in accel[..len].iter_mut().zip(x).zip(y) {
+            let (ax, ay) = pull(xi, yi, xj, yj, mj);
+
+            accel.x += ax;
+            accel.y += ay;
+        }
+    }
+}
@@ -11,12 +11,12 @@ use winit::{
 const WIDTH: f64 = crate::WINDOW_WIDTH as f64;
 const HEIGHT: f64 = crate::WINDOW_HEIGHT as f64;
 
-const MIN_WIDTH: f64 = crate::FB_WIDTH as f64;
-const MIN_HEIGHT: f64 = crate::FB_HEIGHT as f64;
-
+/// Create a window sized to a whole multiple of a `canvas_width` x `canvas_height` framebuffer
 pub fn create_window(
     title: &str,
     event_loop: &EventLoop<()>,
+    canvas_width: u32,
+    canvas_height: u32,
 ) -> Result<(Window, u32, u32, f64), winit::error::OsError> {
     let window = WindowBuilder::new()
         .with_visible(false)
@@ -37,7 +37,7 @@ pub fn create_window(
     let scale = (display_height / HEIGHT * 2.0 / 3.0).round().max(1.0);
 
     let min_size = PhysicalSize::new(WIDTH, HEIGHT).to_logical::<f64>(hidpi);
-    let default = LogicalSize::new(MIN_WIDTH * scale, MIN_HEIGHT * scale);
+    let default = LogicalSize::new(canvas_width as f64 * scale, canvas_height as f64 * scale);
 
     window.set_inner_size(default);
     window.set_min_inner_size(Some(min_size));